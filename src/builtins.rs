@@ -0,0 +1,667 @@
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    io::{BufRead, Read, Write, stdin, stdout},
+    rc::Rc,
+    sync::OnceLock,
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{
+    date,
+    environment::Environment,
+    error::RuntimeError,
+    expr::{Arity, LiteralValue},
+    files, json, math, os, random,
+};
+
+pub type Builtin =
+    fn(Rc<RefCell<Environment>>, &[LiteralValue]) -> Result<LiteralValue, RuntimeError>;
+
+pub fn native(name: &str, arity: Arity, fun: Builtin) -> LiteralValue {
+    LiteralValue::Callable {
+        name: name.to_string(),
+        arity,
+        params: Rc::from([]),
+        keywords: Rc::from([]),
+        fun: Rc::new(fun),
+    }
+}
+
+// named options (`print(x, end: "")`) arrive after the positional arguments,
+// one per keyword in declaration order, nil when the caller left them out
+pub fn native_with_keywords(
+    name: &str,
+    arity: Arity,
+    keywords: &[&str],
+    fun: Builtin,
+) -> LiteralValue {
+    LiteralValue::Callable {
+        name: name.to_string(),
+        arity,
+        params: Rc::from([]),
+        keywords: keywords.iter().map(|k| k.to_string()).collect(),
+        fun: Rc::new(fun),
+    }
+}
+
+// modules that `import "name"` finds before looking on disk
+pub fn native_module(name: &str, args: &[String]) -> Option<Environment> {
+    match name {
+        "math" => Some(math::module()),
+        "fs" => Some(files::module()),
+        "os" => Some(os::module(args)),
+        "date" => Some(date::module()),
+        "random" => Some(random::module()),
+        _ => None,
+    }
+}
+
+// the argument check every numeric builtin starts with
+pub fn number(fun: &str, value: &LiteralValue) -> Result<f64, String> {
+    match value {
+        LiteralValue::Number(x) => Ok(*x),
+        other => Err(format!(
+            "{} expected a number found {}",
+            fun,
+            other.to_type()
+        )),
+    }
+}
+
+pub fn string<'a>(fun: &str, value: &'a LiteralValue) -> Result<&'a str, String> {
+    match value {
+        LiteralValue::StringValue(s) => Ok(s),
+        other => Err(format!(
+            "{} expected a string found {}",
+            fun,
+            other.to_type()
+        )),
+    }
+}
+
+pub fn define_globals(env: &mut Environment) {
+    env.define_const("time", native("time", Arity::exact(0), time_fn));
+    env.define_const("clock", native("clock", Arity::exact(0), clock));
+    env.define_const("sleep", native("sleep", Arity::exact(1), sleep));
+    env.define_const("floor", native("floor", Arity::exact(1), floor));
+    for (name, fun) in [
+        ("print", print as Builtin),
+        ("println", print),
+        ("write", write),
+    ] {
+        env.define_const(
+            name,
+            native_with_keywords(name, Arity::at_least(0), &["sep", "end"], fun),
+        );
+    }
+    env.define_const("format", native("format", Arity::at_least(1), format_fn));
+    env.define_const("range", native("range", Arity::between(1, 3), range));
+    for (name, arity, fun) in [
+        ("type", Arity::exact(1), type_fn as Builtin),
+        ("str", Arity::exact(1), str_fn),
+        ("num", Arity::exact(1), num),
+        ("bool", Arity::exact(1), bool_fn),
+        ("is_callable", Arity::exact(1), is_callable),
+        ("arity", Arity::exact(1), arity),
+        ("name", Arity::exact(1), name_fn),
+    ] {
+        env.define_const(name, native(name, arity, fun));
+    }
+    env.define_const("assert", native("assert", Arity::between(1, 2), assert));
+    env.define_const(
+        "assert_eq",
+        native("assert_eq", Arity::between(2, 3), assert_eq),
+    );
+    env.define_const(
+        "json_parse",
+        native("json_parse", Arity::exact(1), json_parse),
+    );
+    env.define_const(
+        "json_stringify",
+        native("json_stringify", Arity::between(1, 2), json_stringify),
+    );
+    env.define_const("input", native("input", Arity::between(0, 1), input));
+    env.define_const("read_line", native("read_line", Arity::exact(0), read_line));
+    env.define_const("read_all", native("read_all", Arity::exact(0), read_all));
+}
+
+pub fn time_fn(
+    _env: Rc<RefCell<Environment>>,
+    _args: &[LiteralValue],
+) -> Result<LiteralValue, RuntimeError> {
+    let a = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap();
+    Ok(LiteralValue::Number(a.as_secs_f64()))
+}
+
+// seconds on a monotonic clock, for measuring how long something took
+pub fn clock(
+    _env: Rc<RefCell<Environment>>,
+    _args: &[LiteralValue],
+) -> Result<LiteralValue, RuntimeError> {
+    static START: OnceLock<Instant> = OnceLock::new();
+    let start = START.get_or_init(Instant::now);
+    Ok(LiteralValue::Number(start.elapsed().as_secs_f64()))
+}
+
+pub fn sleep(
+    _env: Rc<RefCell<Environment>>,
+    args: &[LiteralValue],
+) -> Result<LiteralValue, RuntimeError> {
+    let ms = number("sleep", &args[0])?;
+    if !ms.is_finite() || ms < 0.0 {
+        return Err(format!("sleep expected a non-negative duration found {}", ms).into());
+    }
+    let duration = Duration::try_from_secs_f64(ms / 1000.0)
+        .map_err(|_| format!("sleep duration {} is too long", ms))?;
+    thread::sleep(duration);
+    Ok(LiteralValue::Nil)
+}
+
+pub fn floor(
+    _env: Rc<RefCell<Environment>>,
+    args: &[LiteralValue],
+) -> Result<LiteralValue, RuntimeError> {
+    Ok(LiteralValue::Number(number("floor", &args[0])?.floor()))
+}
+
+pub fn type_fn(
+    _env: Rc<RefCell<Environment>>,
+    args: &[LiteralValue],
+) -> Result<LiteralValue, RuntimeError> {
+    Ok(LiteralValue::StringValue(args[0].to_type()))
+}
+
+// the text `print` would show
+pub fn str_fn(
+    _env: Rc<RefCell<Environment>>,
+    args: &[LiteralValue],
+) -> Result<LiteralValue, RuntimeError> {
+    Ok(LiteralValue::StringValue(args[0].to_string()))
+}
+
+pub fn num(
+    _env: Rc<RefCell<Environment>>,
+    args: &[LiteralValue],
+) -> Result<LiteralValue, RuntimeError> {
+    match &args[0] {
+        LiteralValue::Number(x) => Ok(LiteralValue::Number(*x)),
+        LiteralValue::True => Ok(LiteralValue::Number(1.0)),
+        LiteralValue::False => Ok(LiteralValue::Number(0.0)),
+        LiteralValue::StringValue(s) => match s.trim().parse::<f64>() {
+            Ok(x) => Ok(LiteralValue::Number(x)),
+            Err(_) => Err(format!("num could not convert {:?} to a number", s).into()),
+        },
+        other => Err(format!("num could not convert {} to a number", other.to_type()).into()),
+    }
+}
+
+// truthiness as `if` sees it: only false and nil are false
+pub fn bool_fn(
+    _env: Rc<RefCell<Environment>>,
+    args: &[LiteralValue],
+) -> Result<LiteralValue, RuntimeError> {
+    Ok(LiteralValue::from_bool(args[0].is_truthy()))
+}
+
+pub fn is_callable(
+    _env: Rc<RefCell<Environment>>,
+    args: &[LiteralValue],
+) -> Result<LiteralValue, RuntimeError> {
+    Ok(LiteralValue::from_bool(matches!(
+        args[0],
+        LiteralValue::Callable { .. }
+    )))
+}
+
+// a number for a fixed arity, otherwise {min, max} with max nil for variadics
+pub fn arity(
+    _env: Rc<RefCell<Environment>>,
+    args: &[LiteralValue],
+) -> Result<LiteralValue, RuntimeError> {
+    let LiteralValue::Callable { arity, .. } = &args[0] else {
+        return Err(format!("arity expected a function found {}", args[0].to_type()).into());
+    };
+    if arity.max == Some(arity.min) {
+        return Ok(LiteralValue::Number(arity.min as f64));
+    }
+    let max = match arity.max {
+        Some(max) => LiteralValue::Number(max as f64),
+        None => LiteralValue::Nil,
+    };
+    Ok(LiteralValue::map(BTreeMap::from([
+        ("min".to_string(), LiteralValue::Number(arity.min as f64)),
+        ("max".to_string(), max),
+    ])))
+}
+
+pub fn name_fn(
+    _env: Rc<RefCell<Environment>>,
+    args: &[LiteralValue],
+) -> Result<LiteralValue, RuntimeError> {
+    match &args[0] {
+        LiteralValue::Callable { name, .. } => Ok(LiteralValue::StringValue(name.clone())),
+        other => Err(format!("name expected a function found {}", other.to_type()).into()),
+    }
+}
+
+// failures are ordinary runtime errors, so they carry the line of the call
+pub fn assert(
+    _env: Rc<RefCell<Environment>>,
+    args: &[LiteralValue],
+) -> Result<LiteralValue, RuntimeError> {
+    if args[0].is_truthy() {
+        return Ok(LiteralValue::Nil);
+    }
+    Err(match args.get(1) {
+        Some(message) => format!("Assertion failed: {}", message),
+        None => "Assertion failed".to_string(),
+    }
+    .into())
+}
+
+pub fn assert_eq(
+    _env: Rc<RefCell<Environment>>,
+    args: &[LiteralValue],
+) -> Result<LiteralValue, RuntimeError> {
+    if args[0] == args[1] {
+        return Ok(LiteralValue::Nil);
+    }
+    let mut message = format!("assert_eq failed: {} != {}", args[0].repr(), args[1].repr());
+    if let Some(note) = args.get(2) {
+        message.push_str(&format!(" ({})", note));
+    }
+    Err(message.into())
+}
+
+pub fn json_parse(
+    _env: Rc<RefCell<Environment>>,
+    args: &[LiteralValue],
+) -> Result<LiteralValue, RuntimeError> {
+    Ok(json::parse(string("json_parse", &args[0])?)?)
+}
+
+// json_stringify(value, indent) with indent in spaces, compact when left out
+pub fn json_stringify(
+    _env: Rc<RefCell<Environment>>,
+    args: &[LiteralValue],
+) -> Result<LiteralValue, RuntimeError> {
+    let indent = match args.get(1) {
+        None | Some(LiteralValue::Nil) => None,
+        Some(indent) => {
+            let width = number("json_stringify", indent)?;
+            if width.fract() != 0.0 || !(0.0..=32.0).contains(&width) {
+                return Err(format!("json_stringify indent must be 0 to 32, got {}", width).into());
+            }
+            Some(width as usize)
+        }
+    };
+    Ok(LiteralValue::StringValue(json::stringify(
+        &args[0], indent,
+    )?))
+}
+
+// one line without its line ending, nil once input runs out
+fn line_from(input: &mut impl BufRead) -> Result<LiteralValue, String> {
+    let mut line = String::new();
+    let read = input
+        .read_line(&mut line)
+        .map_err(|e| format!("could not read stdin: {}", e))?;
+    if read == 0 {
+        return Ok(LiteralValue::Nil);
+    }
+    if line.ends_with('\n') {
+        line.pop();
+        if line.ends_with('\r') {
+            line.pop();
+        }
+    }
+    Ok(LiteralValue::StringValue(line))
+}
+
+// the prompt goes out before the line is read, without a newline
+fn prompt_line(
+    out: &mut impl Write,
+    input: &mut impl BufRead,
+    prompt: Option<&LiteralValue>,
+) -> Result<LiteralValue, String> {
+    if let Some(prompt) = prompt {
+        write!(out, "{}", prompt)
+            .and_then(|_| out.flush())
+            .map_err(|e| format!("could not write to stdout: {}", e))?;
+    }
+    line_from(input)
+}
+
+fn all_from(input: &mut impl Read) -> Result<LiteralValue, String> {
+    let mut text = String::new();
+    input
+        .read_to_string(&mut text)
+        .map_err(|e| format!("could not read stdin: {}", e))?;
+    Ok(LiteralValue::StringValue(text))
+}
+
+pub fn input(
+    _env: Rc<RefCell<Environment>>,
+    args: &[LiteralValue],
+) -> Result<LiteralValue, RuntimeError> {
+    Ok(prompt_line(
+        &mut stdout().lock(),
+        &mut stdin().lock(),
+        args.first(),
+    )?)
+}
+
+pub fn read_line(
+    _env: Rc<RefCell<Environment>>,
+    _args: &[LiteralValue],
+) -> Result<LiteralValue, RuntimeError> {
+    Ok(line_from(&mut stdin().lock())?)
+}
+
+pub fn read_all(
+    _env: Rc<RefCell<Environment>>,
+    _args: &[LiteralValue],
+) -> Result<LiteralValue, RuntimeError> {
+    Ok(all_from(&mut stdin().lock())?)
+}
+
+// range(end), range(start, end) or range(start, end, step)
+pub fn range(
+    _env: Rc<RefCell<Environment>>,
+    args: &[LiteralValue],
+) -> Result<LiteralValue, RuntimeError> {
+    let bounds = args
+        .iter()
+        .map(|arg| number("range", arg))
+        .collect::<Result<Vec<f64>, String>>()?;
+    let (start, end, step) = match bounds.as_slice() {
+        [end] => (0.0, *end, 1.0),
+        [start, end] => (*start, *end, 1.0),
+        [start, end, step, ..] => (*start, *end, *step),
+        [] => unreachable!(),
+    };
+    Ok(LiteralValue::range(start, end, step)?)
+}
+
+fn option_str<'a>(
+    value: &'a LiteralValue,
+    name: &str,
+    default: &'a str,
+) -> Result<&'a str, String> {
+    match value {
+        LiteralValue::Nil => Ok(default),
+        LiteralValue::StringValue(s) => Ok(s),
+        other => Err(format!(
+            "{} expected a string found {}",
+            name,
+            other.to_type()
+        )),
+    }
+}
+
+fn emit(args: &[LiteralValue], end: &str) -> Result<LiteralValue, RuntimeError> {
+    emit_to(&mut stdout().lock(), args, end)?;
+    Ok(LiteralValue::Nil)
+}
+
+// the last two arguments are the `sep` and `end` keywords, nil when not given
+fn emit_to(out: &mut impl Write, args: &[LiteralValue], end: &str) -> Result<(), String> {
+    let (values, options) = args.split_at(args.len() - 2);
+    let sep = option_str(&options[0], "sep", " ")?;
+    let end = option_str(&options[1], "end", end)?;
+    let text = values
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<String>>()
+        .join(sep);
+    write!(out, "{}{}", text, end)
+        .and_then(|_| out.flush())
+        .map_err(|e| format!("could not write to stdout: {}", e))
+}
+
+pub fn print(
+    _env: Rc<RefCell<Environment>>,
+    args: &[LiteralValue],
+) -> Result<LiteralValue, RuntimeError> {
+    emit(args, "\n")
+}
+
+pub fn write(
+    _env: Rc<RefCell<Environment>>,
+    args: &[LiteralValue],
+) -> Result<LiteralValue, RuntimeError> {
+    emit(args, "")
+}
+
+pub fn format_fn(
+    _env: Rc<RefCell<Environment>>,
+    args: &[LiteralValue],
+) -> Result<LiteralValue, RuntimeError> {
+    let LiteralValue::StringValue(template) = &args[0] else {
+        return Err(format!("format expected a string found {}", args[0].to_type()).into());
+    };
+    Ok(LiteralValue::StringValue(format_values(
+        template,
+        &args[1..],
+    )?))
+}
+
+// `{}` takes the next argument, `{1}` a specific one, and an optional
+// `:[[fill]align][width][.precision]` spec follows, e.g. `{:>8.2}`
+pub fn format_values(template: &str, args: &[LiteralValue]) -> Result<String, String> {
+    let mut out = String::new();
+    let mut next = 0;
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let mut field = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => field.push(c),
+                        None => return Err("Unclosed '{' in format string".to_string()),
+                    }
+                }
+                let (index, spec) = field.split_once(':').unwrap_or((&field, ""));
+                let index = if index.is_empty() {
+                    next += 1;
+                    next - 1
+                } else {
+                    index
+                        .parse::<usize>()
+                        .map_err(|_| format!("Invalid format field {{{}}}", field))?
+                };
+                let value = args
+                    .get(index)
+                    .ok_or_else(|| format!("format is missing argument {}", index))?;
+                out.push_str(&apply_spec(value, spec)?);
+            }
+            '}' => return Err("Unmatched '}' in format string".to_string()),
+            c => out.push(c),
+        }
+    }
+    Ok(out)
+}
+
+// Rust's formatter panics on a precision above u16::MAX
+const MAX_SPEC: usize = u16::MAX as usize;
+
+fn apply_spec(value: &LiteralValue, spec: &str) -> Result<String, String> {
+    let chars: Vec<char> = spec.chars().collect();
+    let is_align = |c: &char| matches!(c, '<' | '>' | '^');
+    let (fill, align, rest) = match chars.as_slice() {
+        [fill, align, rest @ ..] if is_align(align) => (*fill, Some(*align), rest),
+        [align, rest @ ..] if is_align(align) => (' ', Some(*align), rest),
+        rest => (' ', None, rest),
+    };
+    let rest: String = rest.iter().collect();
+    let (width, precision) = match rest.split_once('.') {
+        Some((w, p)) => (w, Some(p)),
+        None => (rest.as_str(), None),
+    };
+    let invalid = || format!("Invalid format spec {{:{}}}", spec);
+    let width = if width.is_empty() {
+        0
+    } else {
+        width.parse::<usize>().map_err(|_| invalid())?
+    };
+    let precision = precision
+        .map(|p| p.parse::<usize>())
+        .transpose()
+        .map_err(|_| invalid())?;
+    if width > MAX_SPEC || precision.is_some_and(|p| p > MAX_SPEC) {
+        return Err(format!(
+            "Format spec {{:{}}} is too large, width and precision go up to {}",
+            spec, MAX_SPEC
+        ));
+    }
+
+    let text = match (value, precision) {
+        (LiteralValue::Number(x), Some(p)) => format!("{:.*}", p, x),
+        (LiteralValue::StringValue(s), Some(p)) => s.chars().take(p).collect(),
+        (v, _) => v.to_string(),
+    };
+    let len = text.chars().count();
+    if len >= width {
+        return Ok(text);
+    }
+    let pad = width - len;
+    // numbers line up on the right by default, everything else on the left
+    let align = align.unwrap_or(match value {
+        LiteralValue::Number(_) => '>',
+        _ => '<',
+    });
+    let (left, right) = match align {
+        '<' => (0, pad),
+        '>' => (pad, 0),
+        _ => (pad / 2, pad - pad / 2),
+    };
+    let fill = |n: usize| std::iter::repeat_n(fill, n).collect::<String>();
+    Ok(format!("{}{}{}", fill(left), text, fill(right)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> LiteralValue {
+        LiteralValue::StringValue(s.to_string())
+    }
+
+    fn printed(args: &[LiteralValue], end: &str) -> String {
+        let mut out = vec![];
+        emit_to(&mut out, args, end).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn print_joins_with_sep_and_end() {
+        let n = LiteralValue::Number;
+        let nil = LiteralValue::Nil;
+        assert_eq!(
+            printed(&[n(1.0), text("a"), nil.clone(), nil.clone()], "\n"),
+            "1 a\n"
+        );
+        assert_eq!(printed(&[nil.clone(), nil.clone()], "\n"), "\n");
+        // `write` is `print` without the newline
+        assert_eq!(
+            printed(&[n(1.0), n(2.0), nil.clone(), nil.clone()], ""),
+            "1 2"
+        );
+        assert_eq!(
+            printed(&[n(1.0), n(2.0), text(", "), text("!")], "\n"),
+            "1, 2!"
+        );
+        let mut out = vec![];
+        assert_eq!(
+            emit_to(&mut out, &[n(1.0), n(2.0), nil], ""),
+            Err("sep expected a string found number".to_string())
+        );
+    }
+
+    #[test]
+    fn input_reads_lines_until_the_end() {
+        let mut stdin = std::io::Cursor::new("first\r\nsecond\n\nlast");
+        let mut out = vec![];
+        assert_eq!(
+            prompt_line(&mut out, &mut stdin, Some(&text("name? "))),
+            Ok(text("first"))
+        );
+        assert_eq!(out, b"name? ");
+        assert_eq!(line_from(&mut stdin), Ok(text("second")));
+        assert_eq!(line_from(&mut stdin), Ok(text("")));
+        assert_eq!(all_from(&mut stdin), Ok(text("last")));
+        assert_eq!(line_from(&mut stdin), Ok(LiteralValue::Nil));
+        assert_eq!(
+            prompt_line(&mut out, &mut stdin, None),
+            Ok(LiteralValue::Nil)
+        );
+        assert_eq!(all_from(&mut stdin), Ok(text("")));
+
+        let mut invalid = std::io::Cursor::new(vec![0xff, b'\n']);
+        assert!(line_from(&mut invalid).is_err());
+    }
+
+    #[test]
+    fn format_specs() {
+        let n = LiteralValue::Number;
+        let format = |template: &str, args: &[LiteralValue]| format_values(template, args);
+        assert_eq!(
+            format("{} and {}", &[n(1.0), text("b")]),
+            Ok("1 and b".to_string())
+        );
+        assert_eq!(
+            format("{1}{0}{{}}", &[n(1.0), n(2.0)]),
+            Ok("21{}".to_string())
+        );
+        assert_eq!(
+            format("{:.2}|{:.0}", &[n(1.23456), n(2.7)]),
+            Ok("1.23|3".to_string())
+        );
+        assert_eq!(
+            format("[{:5}][{:5}]", &[n(42.0), text("ab")]),
+            Ok("[   42][ab   ]".to_string())
+        );
+        assert_eq!(
+            format("[{:<5}][{:^6}][{:*>4}]", &[n(7.0), text("mid"), text("x")]),
+            Ok("[7    ][ mid  ][***x]".to_string())
+        );
+        assert_eq!(format("{:-^7.1}", &[n(0.26)]), Ok("--0.3--".to_string()));
+        assert_eq!(format("{:.3}", &[text("abcdef")]), Ok("abc".to_string()));
+        assert_eq!(format("{:2}", &[text("long")]), Ok("long".to_string()));
+
+        assert!(format("{", &[]).is_err());
+        assert!(format("}", &[]).is_err());
+        assert!(format("{} {}", &[n(1.0)]).is_err());
+        assert!(format("{:x}", &[n(1.0)]).is_err());
+        for spec in ["{:.999999}", "{:999999}", "{:99999999999999999999999}"] {
+            assert!(format(spec, &[n(1.0)]).is_err(), "{}", spec);
+        }
+        assert_eq!(format("{:65535}", &[n(1.0)]).map(|s| s.len()), Ok(65535));
+    }
+
+    #[test]
+    fn sleep_rejects_durations_it_cannot_represent() {
+        let env = Rc::new(RefCell::new(Environment::new()));
+        let sleep_for =
+            |ms: f64| sleep(env.clone(), &[LiteralValue::Number(ms)]).map_err(|e| e.to_string());
+        assert_eq!(sleep_for(0.0).ok(), Some(LiteralValue::Nil));
+        assert!(sleep_for(1e300).unwrap_err().ends_with("is too long"));
+        for bad in [-1.0, f64::NAN, f64::INFINITY] {
+            assert!(sleep_for(bad).is_err(), "{}", bad);
+        }
+    }
+}
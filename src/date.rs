@@ -0,0 +1,607 @@
+use std::{cell::RefCell, collections::BTreeMap, rc::Rc};
+
+use crate::{
+    builtins::{Builtin, native, native_with_keywords, number, string, time_fn},
+    environment::Environment,
+    error::RuntimeError,
+    expr::{Arity, LiteralValue},
+};
+
+type EnvRef = Rc<RefCell<Environment>>;
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+const WEEKDAYS: [&str; 7] = [
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+];
+
+// timestamps are seconds since the Unix epoch, like `time()`, and every
+// component is in UTC
+pub fn module() -> Environment {
+    let mut env = Environment::new();
+    let functions: [(&str, Arity, Builtin); 8] = [
+        ("now", Arity::exact(0), time_fn),
+        ("parts", Arity::exact(1), parts),
+        ("timestamp", Arity::between(1, 6), timestamp),
+        ("iso", Arity::exact(1), iso),
+        ("parse_iso", Arity::exact(1), parse_iso),
+        ("format", Arity::exact(2), format),
+        ("parse", Arity::exact(2), parse),
+        ("format_duration", Arity::exact(1), format_duration),
+    ];
+    for (name, arity, fun) in functions {
+        env.define_const(name, native(name, arity, fun));
+    }
+    env.define_const(
+        "duration",
+        native_with_keywords(
+            "duration",
+            Arity::exact(0),
+            &["days", "hours", "minutes", "seconds", "milliseconds"],
+            duration,
+        ),
+    );
+    env
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct DateTime {
+    year: i64,
+    month: u32,
+    day: u32,
+    hour: u32,
+    minute: u32,
+    second: u32,
+    // fraction of the second, in [0, 1)
+    fraction: f64,
+}
+
+// days between 1970-01-01 and the given civil date (proleptic Gregorian)
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let month = month as i64;
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn is_leap(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if is_leap(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl DateTime {
+    fn from_timestamp(ts: f64) -> Result<Self, String> {
+        // roughly +-3 million years, well inside what i64 days can hold
+        if !ts.is_finite() || ts.abs() > 1e14 {
+            return Err(format!("Timestamp {} is out of range", ts));
+        }
+        let secs = ts.floor();
+        let days = (secs / 86400.0).floor();
+        let rest = (secs - days * 86400.0) as u32;
+        let (year, month, day) = civil_from_days(days as i64);
+        Ok(Self {
+            year,
+            month,
+            day,
+            hour: rest / 3600,
+            minute: rest / 60 % 60,
+            second: rest % 60,
+            fraction: ts - secs,
+        })
+    }
+
+    fn new(
+        year: i64,
+        month: u32,
+        day: u32,
+        hour: u32,
+        minute: u32,
+        second: f64,
+    ) -> Result<Self, String> {
+        if year.unsigned_abs() > 1_000_000 {
+            return Err(format!("Year {} is out of range", year));
+        }
+        if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+            return Err(format!("Invalid date {:04}-{:02}-{:02}", year, month, day));
+        }
+        if hour > 23 || minute > 59 || !(0.0..60.0).contains(&second) {
+            return Err(format!("Invalid time {:02}:{:02}:{}", hour, minute, second));
+        }
+        Ok(Self {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second: second.floor() as u32,
+            fraction: second.fract(),
+        })
+    }
+
+    fn days(&self) -> i64 {
+        days_from_civil(self.year, self.month, self.day)
+    }
+
+    fn timestamp(&self) -> f64 {
+        let secs = self.hour as i64 * 3600 + self.minute as i64 * 60 + self.second as i64;
+        (self.days() * 86400 + secs) as f64 + self.fraction
+    }
+
+    // 1 for Monday through 7 for Sunday; the epoch was a Thursday
+    fn weekday(&self) -> u32 {
+        (self.days() + 3).rem_euclid(7) as u32 + 1
+    }
+
+    fn yday(&self) -> u32 {
+        (self.days() - days_from_civil(self.year, 1, 1)) as u32 + 1
+    }
+
+    fn millis(&self) -> u32 {
+        ((self.fraction * 1000.0).floor() as u32).min(999)
+    }
+}
+
+fn timestamp_arg(fun: &str, value: &LiteralValue) -> Result<DateTime, String> {
+    DateTime::from_timestamp(number(fun, value)?)
+}
+
+fn parts(_env: EnvRef, args: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let dt = timestamp_arg("parts", &args[0])?;
+    let n = |x: f64| LiteralValue::Number(x);
+    Ok(LiteralValue::map(BTreeMap::from([
+        ("year".to_string(), n(dt.year as f64)),
+        ("month".to_string(), n(dt.month as f64)),
+        ("day".to_string(), n(dt.day as f64)),
+        ("hour".to_string(), n(dt.hour as f64)),
+        ("minute".to_string(), n(dt.minute as f64)),
+        ("second".to_string(), n(dt.second as f64)),
+        ("millisecond".to_string(), n(dt.millis() as f64)),
+        ("weekday".to_string(), n(dt.weekday() as f64)),
+        ("yday".to_string(), n(dt.yday() as f64)),
+    ])))
+}
+
+// timestamp(year, month, day, [hour, minute, second]) or timestamp(parts)
+fn timestamp(_env: EnvRef, args: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let fields = ["year", "month", "day", "hour", "minute", "second"];
+    let mut values = [0.0; 6];
+    match args {
+        [LiteralValue::Map(entries)] => {
+            let entries = entries.borrow();
+            for (i, field) in fields.iter().enumerate() {
+                match entries.get(*field) {
+                    Some(value) => values[i] = number("timestamp", value)?,
+                    None if i < 3 => {
+                        return Err(format!("timestamp is missing {}", field).into());
+                    }
+                    None => {}
+                }
+            }
+            if let Some(ms) = entries.get("millisecond") {
+                values[5] += number("timestamp", ms)? / 1000.0;
+            }
+        }
+        [_] | [_, _] => {
+            return Err("timestamp expected a map or year, month and day"
+                .to_string()
+                .into());
+        }
+        _ => {
+            for (i, arg) in args.iter().enumerate() {
+                values[i] = number("timestamp", arg)?;
+            }
+        }
+    }
+    if values[..5].iter().any(|x| x.fract() != 0.0) {
+        return Err("timestamp fields other than second must be integers"
+            .to_string()
+            .into());
+    }
+    let [year, month, day, hour, minute, second] = values;
+    // checked here so the message shows the arguments as given
+    if !(1.0..=12.0).contains(&month) || !(1.0..=31.0).contains(&day) {
+        return Err(format!("Invalid date {:04}-{:02}-{:02}", year, month, day).into());
+    }
+    if !(0.0..=23.0).contains(&hour) || !(0.0..=59.0).contains(&minute) {
+        return Err(format!("Invalid time {:02}:{:02}:{}", hour, minute, second).into());
+    }
+    let dt = DateTime::new(
+        year as i64,
+        month as u32,
+        day as u32,
+        hour as u32,
+        minute as u32,
+        second,
+    )?;
+    Ok(LiteralValue::Number(dt.timestamp()))
+}
+
+fn iso(_env: EnvRef, args: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let dt = timestamp_arg("iso", &args[0])?;
+    let pattern = if dt.millis() == 0 {
+        "%Y-%m-%dT%H:%M:%SZ"
+    } else {
+        "%Y-%m-%dT%H:%M:%S.%fZ"
+    };
+    Ok(LiteralValue::StringValue(strftime(&dt, pattern)?))
+}
+
+fn parse_iso(_env: EnvRef, args: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let text = string("parse_iso", &args[0])?;
+    for pattern in ["%Y-%m-%dT%H:%M:%S%z", "%Y-%m-%dT%H:%M%z", "%Y-%m-%d"] {
+        if let Ok(ts) = strptime(text, pattern) {
+            return Ok(LiteralValue::Number(ts));
+        }
+    }
+    Err(format!("Invalid ISO 8601 timestamp {:?}", text).into())
+}
+
+fn format(_env: EnvRef, args: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let dt = timestamp_arg("format", &args[0])?;
+    let pattern = string("format", &args[1])?;
+    Ok(LiteralValue::StringValue(strftime(&dt, pattern)?))
+}
+
+fn parse(_env: EnvRef, args: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let text = string("parse", &args[0])?;
+    let pattern = string("parse", &args[1])?;
+    Ok(LiteralValue::Number(strptime(text, pattern)?))
+}
+
+// duration(hours: 1, minutes: 30) is the number of seconds
+fn duration(_env: EnvRef, args: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let units = [86400.0, 3600.0, 60.0, 1.0, 0.001];
+    let mut total = 0.0;
+    for (value, unit) in args.iter().zip(units) {
+        if *value != LiteralValue::Nil {
+            total += number("duration", value)? * unit;
+        }
+    }
+    Ok(LiteralValue::Number(total))
+}
+
+// 93784.5 -> "1d 2h 3m 4.5s"
+fn format_duration(_env: EnvRef, args: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let secs = number("format_duration", &args[0])?;
+    if !secs.is_finite() {
+        return Err(format!("Duration {} is out of range", secs).into());
+    }
+    let sign = if secs < 0.0 { "-" } else { "" };
+    let mut rest = secs.abs();
+    let mut parts = vec![];
+    for (unit, size) in [("d", 86400.0), ("h", 3600.0), ("m", 60.0)] {
+        let count = (rest / size).floor();
+        if count > 0.0 {
+            parts.push(format!("{}{}", count, unit));
+            rest -= count * size;
+        }
+    }
+    // drop float noise left over from the subtractions
+    let rest = (rest * 1000.0).round() / 1000.0;
+    if rest > 0.0 || parts.is_empty() {
+        parts.push(format!("{}s", rest));
+    }
+    Ok(LiteralValue::StringValue(format!(
+        "{}{}",
+        sign,
+        parts.join(" ")
+    )))
+}
+
+fn strftime(dt: &DateTime, pattern: &str) -> Result<String, String> {
+    let mut out = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        let hour12 = match dt.hour % 12 {
+            0 => 12,
+            h => h,
+        };
+        match chars.next() {
+            Some('Y') => out.push_str(&format!("{:04}", dt.year)),
+            Some('y') => out.push_str(&format!("{:02}", dt.year.rem_euclid(100))),
+            Some('m') => out.push_str(&format!("{:02}", dt.month)),
+            Some('d') => out.push_str(&format!("{:02}", dt.day)),
+            Some('e') => out.push_str(&format!("{:2}", dt.day)),
+            Some('H') => out.push_str(&format!("{:02}", dt.hour)),
+            Some('I') => out.push_str(&format!("{:02}", hour12)),
+            Some('M') => out.push_str(&format!("{:02}", dt.minute)),
+            Some('S') => out.push_str(&format!("{:02}", dt.second)),
+            Some('f') => out.push_str(&format!("{:03}", dt.millis())),
+            Some('p') => out.push_str(if dt.hour < 12 { "AM" } else { "PM" }),
+            Some('j') => out.push_str(&format!("{:03}", dt.yday())),
+            Some('u') => out.push_str(&dt.weekday().to_string()),
+            Some('w') => out.push_str(&(dt.weekday() % 7).to_string()),
+            Some('B') => out.push_str(MONTHS[dt.month as usize - 1]),
+            Some('b') => out.push_str(&MONTHS[dt.month as usize - 1][..3]),
+            Some('A') => out.push_str(WEEKDAYS[dt.weekday() as usize - 1]),
+            Some('a') => out.push_str(&WEEKDAYS[dt.weekday() as usize - 1][..3]),
+            Some('F') => out.push_str(&strftime(dt, "%Y-%m-%d")?),
+            Some('T') => out.push_str(&strftime(dt, "%H:%M:%S")?),
+            Some('s') => out.push_str(&dt.timestamp().floor().to_string()),
+            Some('z') => out.push_str("+0000"),
+            Some('Z') => out.push_str("UTC"),
+            Some('%') => out.push('%'),
+            Some(other) => return Err(format!("Unknown format directive %{}", other)),
+            None => return Err("Format pattern ends with '%'".to_string()),
+        }
+    }
+    Ok(out)
+}
+
+// reads `text` as laid out by `pattern`; %S also takes an optional fraction
+// and %z accepts Z, +HH:MM or +HHMM
+fn strptime(text: &str, pattern: &str) -> Result<f64, String> {
+    let fail = || format!("Could not parse {:?} with pattern {:?}", text, pattern);
+    let mut input = text.chars().peekable();
+    let mut pattern_chars = pattern.chars();
+    let (mut year, mut month, mut day) = (1970, 1, 1);
+    let (mut hour, mut minute, mut second) = (0, 0, 0.0);
+    let mut pm = None;
+    let mut offset = 0;
+
+    let digits = |input: &mut std::iter::Peekable<std::str::Chars>, max: usize| {
+        let mut value = String::new();
+        while value.len() < max && input.peek().is_some_and(|c| c.is_ascii_digit()) {
+            value.push(input.next().unwrap());
+        }
+        value.parse::<i64>().map_err(|_| fail())
+    };
+    let name = |input: &mut std::iter::Peekable<std::str::Chars>, names: &[&str]| {
+        let rest: String = input.clone().collect::<String>().to_lowercase();
+        for (i, full) in names.iter().enumerate() {
+            let full = full.to_lowercase();
+            // the full name first, then the three-letter form
+            for candidate in [full.as_str(), &full[..3]] {
+                if rest.starts_with(candidate) {
+                    for _ in 0..candidate.len() {
+                        input.next();
+                    }
+                    return Some(i);
+                }
+            }
+        }
+        None
+    };
+
+    while let Some(c) = pattern_chars.next() {
+        if c != '%' {
+            if input.next() != Some(c) {
+                return Err(fail());
+            }
+            continue;
+        }
+        match pattern_chars.next() {
+            Some('Y') => {
+                let negative = input.next_if_eq(&'-').is_some();
+                let y = digits(&mut input, 6)?;
+                year = if negative { -y } else { y };
+            }
+            Some('y') => year = 2000 + digits(&mut input, 2)?,
+            Some('m') => month = digits(&mut input, 2)?,
+            Some('d') | Some('e') => {
+                input.next_if_eq(&' ');
+                day = digits(&mut input, 2)?;
+            }
+            Some('H') | Some('I') => hour = digits(&mut input, 2)?,
+            Some('M') => minute = digits(&mut input, 2)?,
+            Some('S') => {
+                second = digits(&mut input, 2)? as f64;
+                if input.next_if_eq(&'.').is_some() {
+                    let mut fraction = String::from("0.");
+                    while let Some(d) = input.next_if(|c| c.is_ascii_digit()) {
+                        fraction.push(d);
+                    }
+                    second += fraction.parse::<f64>().map_err(|_| fail())?;
+                }
+            }
+            Some('f') => {
+                let mut fraction = String::from("0.");
+                while let Some(d) = input.next_if(|c| c.is_ascii_digit()) {
+                    fraction.push(d);
+                }
+                second += fraction.parse::<f64>().map_err(|_| fail())?;
+            }
+            Some('p') => {
+                let rest: String = input.clone().take(2).collect::<String>().to_uppercase();
+                pm = Some(match rest.as_str() {
+                    "AM" => false,
+                    "PM" => true,
+                    _ => return Err(fail()),
+                });
+                input.nth(1);
+            }
+            Some('B') | Some('b') => {
+                month = name(&mut input, &MONTHS).ok_or_else(fail)? as i64 + 1;
+            }
+            // the weekday is implied by the date, so it is only skipped
+            Some('A') | Some('a') => {
+                name(&mut input, &WEEKDAYS).ok_or_else(fail)?;
+            }
+            Some('z') => {
+                if input.next_if(|c| *c == 'Z' || *c == 'z').is_none() {
+                    let sign = match input.next() {
+                        Some('+') => 1,
+                        Some('-') => -1,
+                        _ => return Err(fail()),
+                    };
+                    let hours = digits(&mut input, 2)?;
+                    input.next_if_eq(&':');
+                    let minutes = digits(&mut input, 2)?;
+                    offset = sign * (hours * 3600 + minutes * 60);
+                }
+            }
+            Some('%') => {
+                if input.next() != Some('%') {
+                    return Err(fail());
+                }
+            }
+            Some(other) => return Err(format!("Unknown format directive %{}", other)),
+            None => return Err("Format pattern ends with '%'".to_string()),
+        }
+    }
+    if input.next().is_some() {
+        return Err(fail());
+    }
+    if let Some(pm) = pm {
+        if !(1..=12).contains(&hour) {
+            return Err(fail());
+        }
+        hour = hour % 12 + if pm { 12 } else { 0 };
+    }
+    let small = |x: i64| u32::try_from(x).map_err(|_| fail());
+    let dt = DateTime::new(
+        year,
+        small(month)?,
+        small(day)?,
+        small(hour)?,
+        small(minute)?,
+        second,
+    )?;
+    Ok(dt.timestamp() - offset as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(fun: Builtin, args: &[f64]) -> Result<LiteralValue, String> {
+        let args: Vec<LiteralValue> = args.iter().map(|x| LiteralValue::Number(*x)).collect();
+        fun(Rc::new(RefCell::new(Environment::new())), &args).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn civil_dates_round_trip_across_leap_years_and_the_epoch() {
+        for (year, month, day) in [
+            (1970, 1, 1),
+            (1969, 12, 31),
+            (2000, 2, 29),
+            (1900, 3, 1),
+            (2024, 12, 31),
+            (-1, 1, 1),
+        ] {
+            let days = days_from_civil(year, month, day);
+            assert_eq!(civil_from_days(days), (year, month, day));
+        }
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(
+            days_from_civil(2000, 3, 1) - days_from_civil(2000, 2, 28),
+            2
+        );
+        assert_eq!(
+            days_from_civil(1900, 3, 1) - days_from_civil(1900, 2, 28),
+            1
+        );
+
+        let dt = DateTime::from_timestamp(-0.5).unwrap();
+        assert_eq!((dt.year, dt.month, dt.day, dt.second), (1969, 12, 31, 59));
+        assert_eq!(dt.millis(), 500);
+        assert!(DateTime::from_timestamp(f64::NAN).is_err());
+        assert!(DateTime::new(2023, 2, 29, 0, 0, 0.0).is_err());
+        assert!(DateTime::new(2024, 2, 29, 0, 0, 0.0).is_ok());
+    }
+
+    #[test]
+    fn strftime_covers_names_and_twelve_hour_clock() {
+        // 2024-03-05 00:07:09.25, a Tuesday
+        let dt = DateTime::new(2024, 3, 5, 0, 7, 9.25).unwrap();
+        assert_eq!(
+            strftime(&dt, "%a %b %e %I:%M:%S.%f %p %j %u %w").unwrap(),
+            "Tue Mar  5 12:07:09.250 AM 065 2 2"
+        );
+        assert_eq!(
+            strftime(&dt, "%A, %B %d %Y %%").unwrap(),
+            "Tuesday, March 05 2024 %"
+        );
+        assert!(strftime(&dt, "%q").is_err());
+        assert!(strftime(&dt, "%").is_err());
+    }
+
+    #[test]
+    fn strptime_needs_literals_and_fields_to_match() {
+        assert_eq!(strptime("2024-03-05", "%Y-%m-%d"), Ok(1709596800.0));
+        assert_eq!(
+            strptime("05/Mar/2024 1:30 PM +01:00", "%d/%b/%Y %I:%M %p %z"),
+            Ok(1709641800.0)
+        );
+        for (text, pattern) in [
+            ("2024/03/05", "%Y-%m-%d"),
+            ("2024-03-05x", "%Y-%m-%d"),
+            ("2024-03", "%Y-%m-%d"),
+            ("13:00 PM", "%I:%M %p"),
+            ("Smarch 1 2024", "%B %d %Y"),
+        ] {
+            assert!(strptime(text, pattern).is_err(), "{} {}", text, pattern);
+        }
+        assert_eq!(
+            strptime("2023-02-29", "%Y-%m-%d"),
+            Err("Invalid date 2023-02-29".to_string())
+        );
+    }
+
+    #[test]
+    fn timestamp_reports_the_arguments_it_was_given() {
+        assert_eq!(
+            call(timestamp, &[2024.0, 3.0, 5.0]),
+            Ok(LiteralValue::Number(1709596800.0))
+        );
+        assert_eq!(
+            call(timestamp, &[2024.0, 13.0, 40.0]),
+            Err("[line 0] Invalid date 2024-13-40".to_string())
+        );
+        assert_eq!(
+            call(timestamp, &[2024.0, 2.0, 30.0]),
+            Err("[line 0] Invalid date 2024-02-30".to_string())
+        );
+        assert_eq!(
+            call(timestamp, &[2024.0, 1.0, 1.0, 25.0, 0.0, 0.0]),
+            Err("[line 0] Invalid time 25:00:0".to_string())
+        );
+        assert!(call(timestamp, &[2024.0, 1.5, 1.0]).is_err());
+    }
+}
@@ -1,4 +1,8 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
 
 use crate::expr::LiteralValue;
 
@@ -6,6 +10,8 @@ use crate::expr::LiteralValue;
 pub struct Environment {
     pub values: HashMap<String, LiteralValue>,
     pub enclosing: Option<Rc<RefCell<Environment>>>,
+    // names in this scope that can't be reassigned or redeclared
+    pub constants: HashSet<String>,
 }
 
 impl Environment {
@@ -13,37 +19,43 @@ impl Environment {
         Self {
             enclosing: None,
             values: HashMap::new(),
+            constants: HashSet::new(),
         }
     }
 
-    pub fn define(&mut self, name: &String, value: LiteralValue) {
+    pub fn define(&mut self, name: &str, value: LiteralValue) {
         self.values.insert(name.to_string(), value);
     }
 
-    pub fn get(&self, name: &String) -> Option<LiteralValue> {
+    pub fn define_const(&mut self, name: &str, value: LiteralValue) {
+        self.define(name, value);
+        self.constants.insert(name.to_string());
+    }
+
+    pub fn is_constant(&self, name: &str) -> bool {
+        self.constants.contains(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<LiteralValue> {
         let val = self.values.get(name);
         match (val, &self.enclosing) {
             (Some(v), _) => Some(v.clone()),
-            (None, Some(t)) => {
-                let a = t.borrow_mut().get(name);
-                a
-            }
+            (None, Some(t)) => t.borrow().get(name),
             (None, None) => None,
         }
     }
 
-    pub fn assign(&mut self, name: &str, value_ass: LiteralValue) -> bool {
-        let old_exist = self.values.contains_key(name);
-        match (old_exist, &self.enclosing) {
-            (true, _) => {
-                self.values.insert(name.to_string(), value_ass).unwrap();
-                true
-            }
-            (false, Some(env)) => {
-                env.borrow_mut().assign(name, value_ass);
-                true
+    pub fn assign(&mut self, name: &str, value_ass: LiteralValue) -> Result<(), String> {
+        if self.values.contains_key(name) {
+            if self.is_constant(name) {
+                return Err(format!("Cannot assign to constant {}", name));
             }
-            _ => false,
+            self.values.insert(name.to_string(), value_ass);
+            Ok(())
+        } else if let Some(env) = &self.enclosing {
+            env.borrow_mut().assign(name, value_ass)
+        } else {
+            Err(format!("Variable {} is not declared", name))
         }
     }
 }
@@ -0,0 +1,67 @@
+use std::fmt;
+
+use crate::expr::LiteralValue;
+
+#[derive(Debug, Clone)]
+pub enum RuntimeError {
+    // raised by the interpreter or a builtin
+    Error { message: String, line: usize },
+    // raised by a `throw` statement
+    Thrown { value: LiteralValue, line: usize },
+    // raised by `os.exit`; it unwinds to main without running catch or
+    // finally blocks, so the interpreter and its open files get dropped
+    Exit { code: i32 },
+}
+
+impl RuntimeError {
+    pub fn new(line: usize, message: impl Into<String>) -> Self {
+        Self::Error {
+            message: message.into(),
+            line,
+        }
+    }
+
+    // builtins don't know where they were called from, so the call site
+    // fills the line in on the way out
+    pub fn with_line(self, line: usize) -> Self {
+        match self {
+            Self::Error { message, line: 0 } => Self::Error { message, line },
+            other => other,
+        }
+    }
+
+    // the value a `catch (e)` clause binds
+    pub fn into_value(self) -> LiteralValue {
+        match self {
+            Self::Error { message, line } => LiteralValue::Error { message, line },
+            Self::Thrown { value, .. } => value,
+            Self::Exit { .. } => unreachable!("catch never sees os.exit"),
+        }
+    }
+}
+
+impl From<String> for RuntimeError {
+    fn from(message: String) -> Self {
+        Self::new(0, message)
+    }
+}
+
+impl From<&str> for RuntimeError {
+    fn from(message: &str) -> Self {
+        Self::new(0, message)
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Error { message, line } => write!(f, "[line {}] {}", line, message),
+            Self::Thrown {
+                value: value @ LiteralValue::Error { .. },
+                ..
+            } => write!(f, "{}", value),
+            Self::Thrown { value, line } => write!(f, "[line {}] Uncaught {}", line, value),
+            Self::Exit { code } => write!(f, "Exited with code {}", code),
+        }
+    }
+}
@@ -1,10 +1,73 @@
-use std::{cell::RefCell, fmt::{format, write, Debug}, rc::Rc, result, usize};
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    fmt::{self, Debug},
+    rc::Rc,
+};
 
 use crate::{
-    environment::{self, Environment},
+    environment::Environment,
+    error::RuntimeError,
+    files::{self, FileHandle},
+    strings,
     token::{Literal, Token},
     tokentype::TokenType,
 };
+
+// how many positional arguments a callable takes; `max` is None for variadics
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Arity {
+    pub min: usize,
+    pub max: Option<usize>,
+}
+
+impl Arity {
+    pub fn exact(n: usize) -> Self {
+        Self {
+            min: n,
+            max: Some(n),
+        }
+    }
+
+    pub fn at_least(n: usize) -> Self {
+        Self { min: n, max: None }
+    }
+
+    pub fn between(min: usize, max: usize) -> Self {
+        Self {
+            min,
+            max: Some(max),
+        }
+    }
+
+    pub fn accepts(&self, n: usize) -> bool {
+        n >= self.min && self.max.is_none_or(|max| n <= max)
+    }
+
+    // "add expected 2 arguments but got 3"
+    pub fn mismatch(&self, name: &str, got: usize) -> String {
+        let noun = if self.max == Some(1) && self.min == 1 {
+            "argument"
+        } else {
+            "arguments"
+        };
+        format!("{} expected {} {} but got {}", name, self, noun, got)
+    }
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.max {
+            Some(max) if max == self.min => write!(f, "{}", max),
+            Some(max) => write!(f, "{}..{}", self.min, max),
+            None => write!(f, "{}+", self.min),
+        }
+    }
+}
+
+pub type NativeFn =
+    Rc<dyn Fn(Rc<RefCell<Environment>>, &[LiteralValue]) -> Result<LiteralValue, RuntimeError>>;
+
 #[derive(Clone)]
 pub enum LiteralValue {
     Number(f64),
@@ -12,90 +75,261 @@ pub enum LiteralValue {
     True,
     False,
     Nil,
-    Callable{name:String,arity:usize,fun:Rc<dyn Fn(Rc<RefCell<Environment>>,&Vec<LiteralValue>)->LiteralValue>}
+    Callable {
+        name: String,
+        arity: Arity,
+        // positional parameters that may also be passed as `name: value`
+        params: Rc<[String]>,
+        // extra options only accepted as `name: value` at the call site
+        keywords: Rc<[String]>,
+        fun: NativeFn,
+    },
+    Error {
+        message: String,
+        line: usize,
+    },
+    Module {
+        name: String,
+        env: Rc<RefCell<Environment>>,
+    },
+    List(Rc<RefCell<Vec<LiteralValue>>>),
+    Map(Rc<RefCell<BTreeMap<String, LiteralValue>>>),
+    // lazy `start..end` by `step`, end excluded
+    Range {
+        start: f64,
+        end: f64,
+        step: f64,
+    },
+    File(Rc<FileHandle>),
 }
-impl Debug for LiteralValue{
+impl Debug for LiteralValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f,"{}",self.to_string())
+        write!(f, "{}", self)
     }
 }
 
-
-
-
-
-impl PartialEq for LiteralValue{
+impl PartialEq for LiteralValue {
     fn eq(&self, other: &Self) -> bool {
-        match (self,other) {
-            (Self::Number(a),Self::Number(b))=>a == b,
-            (Self::StringValue(a),Self::StringValue(b))=> a==b,
-            (Self::True,Self::True)=>true,
-            (Self::False,Self::False)=>true,
-            (Self::Callable { name, arity, ..},Self::Callable {
-                name:name2, arity:arity2, .. })=>{
-             name ==  name2 && arity == arity2
+        match (self, other) {
+            (Self::Number(a), Self::Number(b)) => a == b,
+            (Self::StringValue(a), Self::StringValue(b)) => a == b,
+            (Self::True, Self::True) => true,
+            (Self::False, Self::False) => true,
+            (Self::Nil, Self::Nil) => true,
+            (
+                Self::Callable { name, arity, .. },
+                Self::Callable {
+                    name: name2,
+                    arity: arity2,
+                    ..
+                },
+            ) => name == name2 && arity == arity2,
+            (
+                Self::Error { message, line },
+                Self::Error {
+                    message: message2,
+                    line: line2,
+                },
+            ) => message == message2 && line == line2,
+            (Self::Module { env, .. }, Self::Module { env: env2, .. }) => Rc::ptr_eq(env, env2),
+            (Self::List(_), Self::List(_)) | (Self::Map(_), Self::Map(_)) => {
+                self.equals(other, &mut vec![])
             }
-            _=> false
+            (
+                Self::Range { start, end, step },
+                Self::Range {
+                    start: start2,
+                    end: end2,
+                    step: step2,
+                },
+            ) => start == start2 && end == end2 && step == step2,
+            (Self::File(a), Self::File(b)) => Rc::ptr_eq(a, b),
+            _ => false,
         }
     }
 }
+// the address a list or map is shared through, to spot cycles
+fn address<T>(rc: &Rc<T>) -> *const () {
+    Rc::as_ptr(rc) as *const ()
+}
+
 impl LiteralValue {
-    pub fn is_truthy(&self) -> bool {
-        match self {
-            LiteralValue::False | LiteralValue::Nil => false,
-            _ => true,
+    // structural equality for lists and maps. `open` holds the pairs being
+    // compared further up; meeting one again means both sides cycle back the
+    // same way, so that branch is equal
+    fn equals(&self, other: &Self, open: &mut Vec<(*const (), *const ())>) -> bool {
+        let pair = match (self, other) {
+            (Self::List(a), Self::List(b)) => (address(a), address(b)),
+            (Self::Map(a), Self::Map(b)) => (address(a), address(b)),
+            _ => return self == other,
+        };
+        if pair.0 == pair.1 || open.contains(&pair) {
+            return true;
         }
+        open.push(pair);
+        let equal = match (self, other) {
+            (Self::List(a), Self::List(b)) => {
+                let (a, b) = (a.borrow(), b.borrow());
+                a.len() == b.len() && a.iter().zip(b.iter()).all(|(x, y)| x.equals(y, open))
+            }
+            (Self::Map(a), Self::Map(b)) => {
+                let (a, b) = (a.borrow(), b.borrow());
+                a.len() == b.len()
+                    && a.iter()
+                        .zip(b.iter())
+                        .all(|((k, x), (k2, y))| k == k2 && x.equals(y, open))
+            }
+            _ => unreachable!(),
+        };
+        open.pop();
+        equal
     }
 
-    pub fn is_falsy(&self) -> LiteralValue {
+    // Display, with strings quoted when `quote` is set. `open` holds the
+    // lists and maps being written, so a cycle prints as [...] or {...}
+    fn write(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        quote: bool,
+        open: &mut Vec<*const ()>,
+    ) -> fmt::Result {
         match self {
-            Self::Number(x) => {
-                if *x == 0.0 {
-                    Self::True
-                } else {
-                    Self::False
+            LiteralValue::StringValue(s) if quote => write!(f, "{:?}", s),
+            LiteralValue::List(items) => {
+                if open.contains(&address(items)) {
+                    return write!(f, "[...]");
+                }
+                open.push(address(items));
+                write!(f, "[")?;
+                for (i, item) in items.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    item.write(f, true, open)?;
                 }
+                open.pop();
+                write!(f, "]")
             }
-            Self::StringValue(s) => {
-                if s.len() == 0 {
-                    Self::True
-                } else {
-                    Self::False
+            LiteralValue::Map(entries) => {
+                if open.contains(&address(entries)) {
+                    return write!(f, "{{...}}");
+                }
+                open.push(address(entries));
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{:?}: ", key)?;
+                    value.write(f, true, open)?;
                 }
+                open.pop();
+                write!(f, "}}")
             }
-            Self::True => Self::False,
-            Self::False => Self::True,
-            Self::Nil => Self::True,
-            Self::Callable { name, arity, fun }=>panic!("cant use Callable as truthly value")
-
+            other => write!(f, "{}", other),
         }
     }
+
+    // the one truthiness rule, used by `if`, loops, `!`, `and`/`or` and `bool`
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, LiteralValue::False | LiteralValue::Nil)
+    }
+
     pub fn from_bool(b: bool) -> Self {
         if b { Self::True } else { Self::False }
     }
 
+    pub fn list(items: Vec<LiteralValue>) -> Self {
+        Self::List(Rc::new(RefCell::new(items)))
+    }
+
+    pub fn map(entries: BTreeMap<String, LiteralValue>) -> Self {
+        Self::Map(Rc::new(RefCell::new(entries)))
+    }
+
+    pub fn range(start: f64, end: f64, step: f64) -> Result<Self, String> {
+        if step == 0.0 {
+            return Err("range step can't be 0".to_string());
+        }
+        if !(start.is_finite() && end.is_finite() && step.is_finite()) {
+            return Err("range bounds must be finite Numbers".to_string());
+        }
+        Ok(Self::Range { start, end, step })
+    }
+
+    // calls a function value from Rust with positional arguments only
+    pub fn call(
+        &self,
+        env: Rc<RefCell<Environment>>,
+        mut args: Vec<LiteralValue>,
+    ) -> Result<LiteralValue, RuntimeError> {
+        let LiteralValue::Callable {
+            name,
+            arity,
+            keywords,
+            fun,
+            ..
+        } = self
+        else {
+            return Err(format!("{} type is not callable", self.to_type()).into());
+        };
+        if !arity.accepts(args.len()) {
+            return Err(arity.mismatch(name, args.len()).into());
+        }
+        args.extend(vec![LiteralValue::Nil; keywords.len()]);
+        fun(env, &args)
+    }
+
+    // number of values a range yields; 0 for anything else
+    pub fn range_len(&self) -> usize {
+        match self {
+            Self::Range { start, end, step } => ((end - start) / step).ceil().max(0.0) as usize,
+            _ => 0,
+        }
+    }
+
+    // how a value shows up inside a list or map: strings keep their quotes
+    pub fn repr(&self) -> String {
+        match self {
+            LiteralValue::StringValue(s) => format!("{:?}", s),
+            other => other.to_string(),
+        }
+    }
+
+    // the name `type(x)` reports and error messages use
     pub fn to_type(&self) -> String {
         match self {
-            LiteralValue::Number(_) => "Number".to_string(),
-            LiteralValue::StringValue(_) => "String".to_string(),
-            LiteralValue::Nil => "nil".to_string(),
-            LiteralValue::True => "true".to_string(),
-            LiteralValue::False => "false".to_string(),
-            LiteralValue::Callable { name, arity, fun }=>"Callable".to_string()
+            LiteralValue::Number(_) => "number",
+            LiteralValue::StringValue(_) => "string",
+            LiteralValue::Nil => "nil",
+            LiteralValue::True | LiteralValue::False => "bool",
+            LiteralValue::Callable { .. } => "function",
+            LiteralValue::Error { .. } => "error",
+            LiteralValue::Module { .. } => "module",
+            LiteralValue::List(_) => "list",
+            LiteralValue::Map(_) => "map",
+            LiteralValue::Range { .. } => "range",
+            LiteralValue::File(_) => "file",
         }
+        .to_string()
     }
 }
 
-#[derive(Debug,Clone)]
+#[derive(Debug, Clone)]
 pub enum Expr {
     Assign {
         name: Token,
         value: Box<Expr>,
     },
-    Call{
-        callie:Box<Expr>,
+    Call {
+        callie: Box<Expr>,
         paren: Token,
-        args:Vec<Expr>
+        args: Vec<Expr>,
+        named: Vec<(Token, Expr)>,
+    },
+    Get {
+        object: Box<Expr>,
+        name: Token,
     },
     Binary {
         left: Box<Expr>,
@@ -115,23 +349,67 @@ pub enum Expr {
     Variable {
         name: Token,
     },
+    // `x += e`; `operator` is the plain binary operator applied
+    CompoundAssign {
+        target: Box<Expr>,
+        operator: Token,
+        value: Box<Expr>,
+    },
+    // `++x`, `x--`; `operator` is PLUS or MINUS
+    Increment {
+        target: Box<Expr>,
+        operator: Token,
+        prefix: bool,
+    },
     Logical {
         expression: Box<Expr>,
         operator: Token,
         right: Box<Expr>,
     },
+    Ternary {
+        condition: Box<Expr>,
+        then: Box<Expr>,
+        els: Box<Expr>,
+    },
+    List {
+        items: Vec<Expr>,
+    },
+    Map {
+        brace: Token,
+        entries: Vec<(Expr, Expr)>,
+    },
+    Index {
+        object: Box<Expr>,
+        bracket: Token,
+        index: Box<Expr>,
+    },
+    IndexSet {
+        object: Box<Expr>,
+        bracket: Token,
+        index: Box<Expr>,
+        value: Box<Expr>,
+    },
 }
 
-#[allow(warnings)]
-impl ToString for LiteralValue {
-    fn to_string(&self) -> String {
+impl fmt::Display for LiteralValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            LiteralValue::Number(x) => x.to_string(),
-            LiteralValue::StringValue(x) => x.clone(),
-            LiteralValue::True => "true".to_string(),
-            LiteralValue::False => "false".to_string(),
-            LiteralValue::Nil => "nil".to_string(),
-            LiteralValue::Callable { name, arity, fun }=>format!("{name}/{arity}")
+            LiteralValue::Number(x) => write!(f, "{}", x),
+            LiteralValue::StringValue(x) => write!(f, "{}", x),
+            LiteralValue::True => write!(f, "true"),
+            LiteralValue::False => write!(f, "false"),
+            LiteralValue::Nil => write!(f, "nil"),
+            LiteralValue::Callable { name, arity, .. } => write!(f, "{name}/{arity}"),
+            LiteralValue::Error { message, line } => write!(f, "[line {}] {}", line, message),
+            LiteralValue::Module { name, .. } => write!(f, "<module {}>", name),
+            LiteralValue::List(_) | LiteralValue::Map(_) => self.write(f, false, &mut vec![]),
+            LiteralValue::Range { start, end, step } if *step == 1.0 => {
+                write!(f, "{}..{}", start, end)
+            }
+            LiteralValue::Range { start, end, step } => {
+                write!(f, "range({}, {}, {})", start, end, step)
+            }
+            LiteralValue::File(file) => write!(f, "<file {}>", file.path),
         }
     }
 }
@@ -144,7 +422,7 @@ fn unwrap_as_string(literal: Option<Literal>) -> String {
 }
 fn unwrap_as_f64(literal: Option<Literal>) -> f64 {
     match literal {
-        Some(Literal::FLiteral(x)) => x as f64,
+        Some(Literal::FLiteral(x)) => x,
         _ => panic!("could not unwrap"),
     }
 }
@@ -163,72 +441,181 @@ impl LiteralValue {
         }
     }
 }
-#[allow(warnings)]
-impl ToString for Expr {
-    fn to_string(&self) -> String {
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Expr::Call { callie, paren, args }=>{
-                format!("{:?}",callie)
-            }
-            Expr::Logical {
-                expression,
-                operator,
-                right,
-            } => "".to_string(),
-            Expr::Assign { name, value } => {
-                format!("{name:?} = {}", value.to_string())
-            }
+            Expr::Call { callie, .. } => write!(f, "{:?}", callie),
+            Expr::Get { object, name } => write!(f, "(. {} {})", object, name.lexeme),
+            Expr::Logical { .. } => Ok(()),
+            Expr::Ternary {
+                condition,
+                then,
+                els,
+            } => write!(f, "(? {} {} {})", condition, then, els),
+            Expr::Assign { name, value } => write!(f, "{name:?} = {}", value),
             Expr::Binary {
                 left,
                 operator,
                 right,
+            } => write!(f, "({} {} {})", operator.lexeme, left, right),
+            Expr::Unary { operator, right } => write!(f, "({} {})", operator.lexeme, right),
+            Expr::Literal { value } => write!(f, "{}", value),
+            Expr::Grouping { expression } => write!(f, "(group {})", expression),
+            Expr::Variable { name } => write!(f, "(var {})", name.lexeme),
+            Expr::CompoundAssign {
+                target,
+                operator,
+                value,
+            } => write!(f, "({}= {} {})", operator.lexeme, target, value),
+            Expr::Increment {
+                target,
+                operator,
+                prefix,
             } => {
-                format!(
-                    "({} {} {})",
-                    operator.lexeme,
-                    left.to_string(),
-                    right.to_string()
-                )
-            }
-            Expr::Unary { operator, right } => {
-                let operator_str = operator.lexeme.clone();
-                let right_str = (*right).to_string();
-                format!("({} {})", operator_str, right_str)
+                let op = operator.lexeme.repeat(2);
+                if *prefix {
+                    write!(f, "({} {})", op, target)
+                } else {
+                    write!(f, "({} {})", target, op)
+                }
             }
-            Expr::Literal { value } => {
-                format!("{}", value.to_string())
+            Expr::List { items } => {
+                let items: Vec<String> = items.iter().map(|e| e.to_string()).collect();
+                write!(f, "(list {})", items.join(" "))
             }
-            Expr::Grouping { expression } => {
-                format!("(group {})", (*expression).to_string())
+            Expr::Map { entries, .. } => {
+                let entries: Vec<String> = entries
+                    .iter()
+                    .map(|(k, v)| format!("{}: {}", k, v))
+                    .collect();
+                write!(f, "(map {})", entries.join(" "))
             }
-            Expr::Variable { name } => format!("(var {})", name.lexeme),
+            Expr::Index { object, index, .. } => write!(f, "(index {} {})", object, index),
+            Expr::IndexSet {
+                object,
+                index,
+                value,
+                ..
+            } => write!(f, "(index= {} {} {})", object, index, value),
         }
     }
 }
 
 impl Expr {
-    pub fn print(&self) {
-        println!("=> {}", self.to_string());
-    }
-    pub fn eval(&self, env: Rc<RefCell<Environment>>) -> Result<LiteralValue, String> {
+    pub fn eval(&self, env: Rc<RefCell<Environment>>) -> Result<LiteralValue, RuntimeError> {
         match self {
             Expr::Call {
-                callie, 
-                paren, 
-                args }=>{
+                callie,
+                paren,
+                args,
+                named,
+            } => {
                 let evals = callie.eval(env.clone())?;
                 match evals {
-                    LiteralValue::Callable { name, arity, fun }=>{
-                        if arity != args.len() {
-                            return Err("Wrong args".to_string());
+                    LiteralValue::Callable {
+                        name,
+                        arity,
+                        params,
+                        keywords,
+                        fun,
+                    } => {
+                        // positional arguments fill the first slots, named ones
+                        // the slot of the parameter they name
+                        let mut slots: Vec<Option<LiteralValue>> = args
+                            .iter()
+                            .map(|x| x.eval(env.clone()).map(Some))
+                            .collect::<Result<_, _>>()?;
+                        let mut options = vec![LiteralValue::Nil; keywords.len()];
+                        for (key, value) in named {
+                            if let Some(slot) = params.iter().position(|p| *p == key.lexeme) {
+                                if slots.len() <= slot {
+                                    slots.resize(slot + 1, None);
+                                }
+                                if slots[slot].is_some() {
+                                    return Err(RuntimeError::new(
+                                        key.line,
+                                        format!(
+                                            "{} got multiple values for argument {}",
+                                            name, key.lexeme
+                                        ),
+                                    ));
+                                }
+                                slots[slot] = Some(value.eval(env.clone())?);
+                            } else if let Some(slot) =
+                                keywords.iter().position(|k| *k == key.lexeme)
+                            {
+                                options[slot] = value.eval(env.clone())?;
+                            } else {
+                                return Err(RuntimeError::new(
+                                    key.line,
+                                    format!("{} got an unexpected argument {}", name, key.lexeme),
+                                ));
+                            }
                         }
-                        let args:Vec<LiteralValue> = args.iter().map(|x| x.eval(env.clone()).unwrap()).collect();
-                        Ok(fun(env.clone(),&args))
+                        if !arity.accepts(slots.len()) {
+                            return Err(RuntimeError::new(
+                                paren.line,
+                                arity.mismatch(&name, slots.len()),
+                            ));
+                        }
+                        if let Some(missing) = slots[..arity.min].iter().position(|s| s.is_none()) {
+                            return Err(RuntimeError::new(
+                                paren.line,
+                                format!("{} is missing argument {}", name, params[missing]),
+                            ));
+                        }
+                        // skipped optional parameters read as nil, like a default
+                        let mut args: Vec<LiteralValue> = slots
+                            .into_iter()
+                            .map(|s| s.unwrap_or(LiteralValue::Nil))
+                            .collect();
+                        args.extend(options);
+                        fun(env.clone(), &args).map_err(|e| e.with_line(paren.line))
                     }
-                    other=>Err(format!("{:?} type is not callable",other))
+                    other => Err(RuntimeError::new(
+                        paren.line,
+                        format!("{:?} type is not callable", other),
+                    )),
                 }
-
-            },
+            }
+            Expr::Get { object, name } => {
+                let object = object.eval(env)?;
+                match (&object, name.lexeme.as_str()) {
+                    (LiteralValue::Error { message, .. }, "message") => {
+                        Ok(LiteralValue::StringValue(message.clone()))
+                    }
+                    (LiteralValue::Error { line, .. }, "line") => {
+                        Ok(LiteralValue::Number(*line as f64))
+                    }
+                    // maps double as simple objects: `point.x`, `it.next()`
+                    (LiteralValue::Map(entries), member) => Ok(entries
+                        .borrow()
+                        .get(member)
+                        .cloned()
+                        .unwrap_or(LiteralValue::Nil)),
+                    (LiteralValue::Module { name: module, env }, member) => {
+                        env.borrow().values.get(member).cloned().ok_or_else(|| {
+                            RuntimeError::new(
+                                name.line,
+                                format!("Module {} has no member {}", module, member),
+                            )
+                        })
+                    }
+                    (LiteralValue::File(file), member) => {
+                        files::method(file, member).ok_or_else(|| {
+                            RuntimeError::new(name.line, format!("File has no method {}", member))
+                        })
+                    }
+                    (LiteralValue::StringValue(s), member) => strings::method(s, member)
+                        .ok_or_else(|| {
+                            RuntimeError::new(name.line, format!("String has no method {}", member))
+                        }),
+                    _ => Err(RuntimeError::new(
+                        name.line,
+                        format!("{} has no property {}", object.to_type(), name.lexeme),
+                    )),
+                }
+            }
             Expr::Logical {
                 expression,
                 operator,
@@ -239,25 +626,114 @@ impl Expr {
                     if left.is_truthy() {
                         return Ok(left);
                     }
-                } else {
-                    if !left.is_truthy() {
+                } else if operator.token_type == TokenType::QUESTION_QUESTION {
+                    if left != LiteralValue::Nil {
                         return Ok(left);
                     }
+                } else if !left.is_truthy() {
+                    return Ok(left);
                 }
                 right.eval(env)
             }
+            Expr::Ternary {
+                condition,
+                then,
+                els,
+            } => {
+                if condition.eval(env.clone())?.is_truthy() {
+                    then.eval(env)
+                } else {
+                    els.eval(env)
+                }
+            }
             Expr::Assign { name, value } => {
                 let new_value = (*value).eval(env.clone())?;
-                let assign_success = env.borrow_mut().assign(&name.lexeme, new_value.clone());
-                if assign_success {
-                    Ok(new_value)
-                } else {
-                    Err(format!("variable {} not declared", &name.lexeme))
+                env.borrow_mut()
+                    .assign(&name.lexeme, new_value.clone())
+                    .map_err(|e| RuntimeError::new(name.line, e))?;
+                Ok(new_value)
+            }
+            Expr::CompoundAssign {
+                target,
+                operator,
+                value,
+            } => {
+                let (_, new_value) = update_target(target, env.clone(), |current| {
+                    let value = value.eval(env.clone())?;
+                    binary_op(current, operator, &value)
+                })?;
+                Ok(new_value)
+            }
+            Expr::Increment {
+                target,
+                operator,
+                prefix,
+            } => {
+                let (current, new_value) = update_target(target, env, |current| {
+                    if !matches!(current, LiteralValue::Number(_)) {
+                        return Err(RuntimeError::new(
+                            operator.line,
+                            format!(
+                                "{} expected a number found {}",
+                                operator.lexeme.repeat(2),
+                                current.to_type()
+                            ),
+                        ));
+                    }
+                    binary_op(current, operator, &LiteralValue::Number(1.0))
+                })?;
+                Ok(if *prefix { new_value } else { current })
+            }
+            Expr::List { items } => {
+                let items = items
+                    .iter()
+                    .map(|x| x.eval(env.clone()))
+                    .collect::<Result<_, _>>()?;
+                Ok(LiteralValue::list(items))
+            }
+            Expr::Map { brace, entries } => {
+                let mut map = BTreeMap::new();
+                for (key, value) in entries {
+                    let key = match key.eval(env.clone())? {
+                        LiteralValue::StringValue(k) => k,
+                        other => {
+                            return Err(RuntimeError::new(
+                                brace.line,
+                                format!("Map keys must be strings found {}", other.to_type()),
+                            ));
+                        }
+                    };
+                    map.insert(key, value.eval(env.clone())?);
                 }
+                Ok(LiteralValue::map(map))
+            }
+            Expr::Index {
+                object,
+                bracket,
+                index,
+            } => {
+                let object = object.eval(env.clone())?;
+                let index = index.eval(env)?;
+                index_get(&object, &index, bracket)
+            }
+            Expr::IndexSet {
+                object,
+                bracket,
+                index,
+                value,
+            } => {
+                let object = object.eval(env.clone())?;
+                let index = index.eval(env.clone())?;
+                let value = value.eval(env)?;
+                index_set(&object, index, value.clone(), bracket)?;
+                Ok(value)
             }
             Expr::Variable { name } => match env.borrow_mut().get(&name.lexeme) {
                 Some(v) => Ok(v.clone()),
-                None => Err(format!("Variable {} is not declared ", name.lexeme)),
+                None => Err(RuntimeError::new(
+                    name.line,
+                    format!("Variable {} is not declared ", name.lexeme),
+                )),
             },
             Expr::Literal { value } => Ok(value.clone()),
             Expr::Grouping { expression } => expression.eval(env),
@@ -265,11 +741,22 @@ impl Expr {
                 let right = right.eval(env)?;
                 match (&right, operator.token_type) {
                     (LiteralValue::Number(x), TokenType::MINUS) => Ok(LiteralValue::Number(-x)),
-                    (_, TokenType::MINUS) => {
-                        Err(format!("Minus is not implemented for {}", right.to_type()))
+                    (_, TokenType::MINUS) => Err(RuntimeError::new(
+                        operator.line,
+                        format!("Minus is not implemented for {}", right.to_type()),
+                    )),
+                    (LiteralValue::Number(x), TokenType::TILDE) => {
+                        Ok(LiteralValue::Number(!to_int(*x, operator)? as f64))
                     }
-                    (any, TokenType::BANG) => Ok(any.is_falsy()),
-                    (_, ttype) => Err(format!("{:?} is not a valid unary operator", ttype)),
+                    (_, TokenType::TILDE) => Err(RuntimeError::new(
+                        operator.line,
+                        format!("~ expected an integer found {}", right.to_type()),
+                    )),
+                    (any, TokenType::BANG) => Ok(LiteralValue::from_bool(!any.is_truthy())),
+                    (_, ttype) => Err(RuntimeError::new(
+                        operator.line,
+                        format!("{:?} is not a valid unary operator", ttype),
+                    )),
                 }
             }
 
@@ -280,103 +767,272 @@ impl Expr {
             } => {
                 let left = left.eval(env.clone())?;
                 let right = right.eval(env)?;
+                binary_op(&left, operator, &right)
+            }
+        }
+    }
+}
 
-                match (&left, operator.token_type, &right) {
-                    (LiteralValue::Number(x), TokenType::Modulus, LiteralValue::Number(y)) => {
-                        Ok(LiteralValue::Number(x % y))
-                    }
-                    (LiteralValue::Number(x), TokenType::PLUS, LiteralValue::Number(y)) => {
-                        Ok(LiteralValue::Number(x + y))
-                    }
-                    (LiteralValue::Number(x), TokenType::MINUS, LiteralValue::Number(y)) => {
-                        Ok(LiteralValue::Number(x - y))
-                    }
-                    (LiteralValue::Number(x), TokenType::SLASH, LiteralValue::Number(y)) => {
-                        Ok(LiteralValue::Number(x / y))
-                    }
+// reads an assignable target once, writes `f(current)` back to it and
+// returns (old, new); the object and index of `xs[i()] += 1` are only
+// evaluated a single time
+fn update_target(
+    target: &Expr,
+    env: Rc<RefCell<Environment>>,
+    f: impl FnOnce(&LiteralValue) -> Result<LiteralValue, RuntimeError>,
+) -> Result<(LiteralValue, LiteralValue), RuntimeError> {
+    match target {
+        Expr::Variable { name } => {
+            let current = target.eval(env.clone())?;
+            let new_value = f(&current)?;
+            env.borrow_mut()
+                .assign(&name.lexeme, new_value.clone())
+                .map_err(|e| RuntimeError::new(name.line, e))?;
+            Ok((current, new_value))
+        }
+        Expr::Index {
+            object,
+            bracket,
+            index,
+        } => {
+            let object = object.eval(env.clone())?;
+            let index = index.eval(env)?;
+            let current = index_get(&object, &index, bracket)?;
+            let new_value = f(&current)?;
+            index_set(&object, index, new_value.clone(), bracket)?;
+            Ok((current, new_value))
+        }
+        other => Err(format!("Invalid assignment target {}", other).into()),
+    }
+}
 
-                    (LiteralValue::Number(x), TokenType::STAR, LiteralValue::Number(y)) => {
-                        Ok(LiteralValue::Number(x * y))
-                    }
+// negative indices count from the end
+fn list_index(i: f64, len: usize, bracket: &Token) -> Result<usize, RuntimeError> {
+    let index = if i < 0.0 { i + len as f64 } else { i };
+    if i.fract() != 0.0 || index < 0.0 || index >= len as f64 {
+        return Err(RuntimeError::new(
+            bracket.line,
+            format!("Index {} out of range for length {}", i, len),
+        ));
+    }
+    Ok(index as usize)
+}
 
-                    (LiteralValue::Number(x), TokenType::GREATER, LiteralValue::Number(y)) => {
-                        Ok(LiteralValue::from_bool(x > y))
-                    }
+pub fn index_get(
+    object: &LiteralValue,
+    index: &LiteralValue,
+    bracket: &Token,
+) -> Result<LiteralValue, RuntimeError> {
+    match (object, index) {
+        (LiteralValue::List(items), LiteralValue::Number(i)) => {
+            let items = items.borrow();
+            Ok(items[list_index(*i, items.len(), bracket)?].clone())
+        }
+        // strings index by character, and a range takes a slice: `s[1..3]`
+        (LiteralValue::StringValue(s), LiteralValue::Number(i)) => {
+            let i = list_index(*i, s.chars().count(), bracket)?;
+            Ok(LiteralValue::StringValue(
+                s.chars().nth(i).unwrap().to_string(),
+            ))
+        }
+        (LiteralValue::StringValue(s), LiteralValue::Range { start, end, step }) => {
+            if *step != 1.0 {
+                return Err(RuntimeError::new(
+                    bracket.line,
+                    "Cannot slice with a stepped range",
+                ));
+            }
+            strings::slice(s, *start, *end)
+                .map(LiteralValue::StringValue)
+                .map_err(|e| RuntimeError::new(bracket.line, e))
+        }
+        // missing keys read as nil so `m["k"] ?? default` works
+        (LiteralValue::Map(entries), LiteralValue::StringValue(key)) => Ok(entries
+            .borrow()
+            .get(key)
+            .cloned()
+            .unwrap_or(LiteralValue::Nil)),
+        _ => Err(RuntimeError::new(
+            bracket.line,
+            format!("Cannot index {} with {}", object.to_type(), index.to_type()),
+        )),
+    }
+}
 
-                    (
-                        LiteralValue::Number(x),
-                        TokenType::GREATER_EQUAL,
-                        LiteralValue::Number(y),
-                    ) => Ok(LiteralValue::from_bool(x >= y)),
+pub fn index_set(
+    object: &LiteralValue,
+    index: LiteralValue,
+    value: LiteralValue,
+    bracket: &Token,
+) -> Result<(), RuntimeError> {
+    match (object, index) {
+        (LiteralValue::List(items), LiteralValue::Number(i)) => {
+            let mut items = items.borrow_mut();
+            let i = list_index(i, items.len(), bracket)?;
+            items[i] = value;
+            Ok(())
+        }
+        (LiteralValue::Map(entries), LiteralValue::StringValue(key)) => {
+            entries.borrow_mut().insert(key, value);
+            Ok(())
+        }
+        (object, index) => Err(RuntimeError::new(
+            bracket.line,
+            format!(
+                "Cannot assign to {} with {} index",
+                object.to_type(),
+                index.to_type()
+            ),
+        )),
+    }
+}
 
-                    (LiteralValue::Number(x), TokenType::LESS, LiteralValue::Number(y)) => {
-                        Ok(LiteralValue::from_bool(x < y))
-                    }
+// bitwise operators work on whole numbers that survive the trip through f64
+fn to_int(x: f64, operator: &Token) -> Result<i64, RuntimeError> {
+    if x.fract() == 0.0 && x.abs() <= 9007199254740992.0 {
+        Ok(x as i64)
+    } else {
+        Err(RuntimeError::new(
+            operator.line,
+            format!("{} expected an integer found {}", operator.lexeme, x),
+        ))
+    }
+}
 
-                    (LiteralValue::Number(x), TokenType::LESS_EQUAL, LiteralValue::Number(y)) => {
-                        Ok(LiteralValue::from_bool(x <= y))
-                    }
-                    (LiteralValue::StringValue(x), TokenType::PLUS, LiteralValue::Number(y)) => {
-                        Ok(LiteralValue::StringValue(format!("{}{}", x, y)))
-                    }
+fn bitwise(x: f64, operator: &Token, y: f64) -> Result<LiteralValue, RuntimeError> {
+    let (x, y) = (to_int(x, operator)?, to_int(y, operator)?);
+    let result = match operator.token_type {
+        TokenType::AMPERSAND => x & y,
+        TokenType::PIPE => x | y,
+        TokenType::CARET => x ^ y,
+        _ => {
+            if !(0..64).contains(&y) {
+                return Err(RuntimeError::new(
+                    operator.line,
+                    format!("Shift amount {} out of range", y),
+                ));
+            }
+            if operator.token_type == TokenType::LESS_LESS {
+                x << y
+            } else {
+                x >> y
+            }
+        }
+    };
+    Ok(LiteralValue::Number(result as f64))
+}
 
-                    (LiteralValue::StringValue(_), _, LiteralValue::Number(_)) => {
-                        Err("Cannot operate on String and number".to_string())
-                    }
+fn binary_op(
+    left: &LiteralValue,
+    operator: &Token,
+    right: &LiteralValue,
+) -> Result<LiteralValue, RuntimeError> {
+    match (left, operator.token_type, right) {
+        (
+            LiteralValue::Number(_),
+            TokenType::Modulus | TokenType::TILDE_SLASH,
+            LiteralValue::Number(y),
+        ) if *y == 0.0 => Err(RuntimeError::new(operator.line, "Division by zero")),
+        (LiteralValue::Number(x), TokenType::Modulus, LiteralValue::Number(y)) => {
+            Ok(LiteralValue::Number(x % y))
+        }
+        (LiteralValue::Number(x), TokenType::PLUS, LiteralValue::Number(y)) => {
+            Ok(LiteralValue::Number(x + y))
+        }
+        (LiteralValue::Number(x), TokenType::MINUS, LiteralValue::Number(y)) => {
+            Ok(LiteralValue::Number(x - y))
+        }
+        (LiteralValue::Number(x), TokenType::SLASH, LiteralValue::Number(y)) => {
+            Ok(LiteralValue::Number(x / y))
+        }
 
-                    (LiteralValue::Number(_), _, LiteralValue::StringValue(_)) => {
-                        Err("Cannot operate on String and number".to_string())
-                    }
+        (LiteralValue::Number(x), TokenType::STAR, LiteralValue::Number(y)) => {
+            Ok(LiteralValue::Number(x * y))
+        }
 
-                    (
-                        LiteralValue::StringValue(x),
-                        TokenType::PLUS,
-                        LiteralValue::StringValue(y),
-                    ) => Ok(LiteralValue::StringValue(format!("{}{}", x, y))),
-
-                    (x, TokenType::BANG_EQUAL, y) => Ok(LiteralValue::from_bool(x != y)),
-
-                    (x, TokenType::EQUAL_EQUAL, y) => Ok(LiteralValue::from_bool(x == y)),
-                    (
-                        LiteralValue::StringValue(x),
-                        TokenType::GREATER,
-                        LiteralValue::StringValue(y),
-                    ) => Ok(LiteralValue::from_bool(x > y)),
-
-                    (
-                        LiteralValue::StringValue(x),
-                        TokenType::GREATER_EQUAL,
-                        LiteralValue::StringValue(y),
-                    ) => Ok(LiteralValue::from_bool(x >= y)),
-
-                    (
-                        LiteralValue::StringValue(x),
-                        TokenType::LESS,
-                        LiteralValue::StringValue(y),
-                    ) => Ok(LiteralValue::from_bool(x < y)),
-
-                    (
-                        LiteralValue::StringValue(x),
-                        TokenType::LESS_EQUAL,
-                        LiteralValue::StringValue(y),
-                    ) => Ok(LiteralValue::from_bool(x <= y)),
-
-                    (x, ttype, y) => Err(format!("{:?}  not impl for {:?} and {:?}", ttype, x, y)),
-                }
-            }
+        (LiteralValue::Number(x), TokenType::STAR_STAR, LiteralValue::Number(y)) => {
+            Ok(LiteralValue::Number(x.powf(*y)))
+        }
+
+        (LiteralValue::Number(x), TokenType::DOT_DOT, LiteralValue::Number(y)) => {
+            LiteralValue::range(*x, *y, 1.0).map_err(|e| RuntimeError::new(operator.line, e))
+        }
+
+        (LiteralValue::Number(x), TokenType::TILDE_SLASH, LiteralValue::Number(y)) => {
+            Ok(LiteralValue::Number((x / y).floor()))
+        }
+
+        (
+            LiteralValue::Number(x),
+            TokenType::AMPERSAND
+            | TokenType::PIPE
+            | TokenType::CARET
+            | TokenType::LESS_LESS
+            | TokenType::GREATER_GREATER,
+            LiteralValue::Number(y),
+        ) => bitwise(*x, operator, *y),
+
+        (LiteralValue::Number(x), TokenType::GREATER, LiteralValue::Number(y)) => {
+            Ok(LiteralValue::from_bool(x > y))
+        }
+
+        (LiteralValue::Number(x), TokenType::GREATER_EQUAL, LiteralValue::Number(y)) => {
+            Ok(LiteralValue::from_bool(x >= y))
+        }
+
+        (LiteralValue::Number(x), TokenType::LESS, LiteralValue::Number(y)) => {
+            Ok(LiteralValue::from_bool(x < y))
+        }
+
+        (LiteralValue::Number(x), TokenType::LESS_EQUAL, LiteralValue::Number(y)) => {
+            Ok(LiteralValue::from_bool(x <= y))
+        }
+        (LiteralValue::StringValue(x), TokenType::PLUS, LiteralValue::Number(y)) => {
+            Ok(LiteralValue::StringValue(format!("{}{}", x, y)))
+        }
 
-            _ => todo!(),
+        (LiteralValue::StringValue(_), _, LiteralValue::Number(_)) => Err(RuntimeError::new(
+            operator.line,
+            "Cannot operate on string and number",
+        )),
+
+        (LiteralValue::Number(_), _, LiteralValue::StringValue(_)) => Err(RuntimeError::new(
+            operator.line,
+            "Cannot operate on string and number",
+        )),
+
+        (LiteralValue::StringValue(x), TokenType::PLUS, LiteralValue::StringValue(y)) => {
+            Ok(LiteralValue::StringValue(format!("{}{}", x, y)))
+        }
+
+        (x, TokenType::BANG_EQUAL, y) => Ok(LiteralValue::from_bool(x != y)),
+
+        (x, TokenType::EQUAL_EQUAL, y) => Ok(LiteralValue::from_bool(x == y)),
+        (LiteralValue::StringValue(x), TokenType::GREATER, LiteralValue::StringValue(y)) => {
+            Ok(LiteralValue::from_bool(x > y))
+        }
+
+        (LiteralValue::StringValue(x), TokenType::GREATER_EQUAL, LiteralValue::StringValue(y)) => {
+            Ok(LiteralValue::from_bool(x >= y))
         }
+
+        (LiteralValue::StringValue(x), TokenType::LESS, LiteralValue::StringValue(y)) => {
+            Ok(LiteralValue::from_bool(x < y))
+        }
+
+        (LiteralValue::StringValue(x), TokenType::LESS_EQUAL, LiteralValue::StringValue(y)) => {
+            Ok(LiteralValue::from_bool(x <= y))
+        }
+
+        (x, ttype, y) => Err(RuntimeError::new(
+            operator.line,
+            format!("{:?}  not impl for {:?} and {:?}", ttype, x, y),
+        )),
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use core::num;
-    use std::fmt::Binary;
-
-    use crate::token::Literal;
-
     use super::*;
 
     #[test]
@@ -414,4 +1070,46 @@ mod tests {
         let result = ast.to_string();
         assert_eq!(result, "(* (- 123) (group 45.67))")
     }
+
+    // [1, <itself>], built the way `xs[1] = xs` would
+    fn self_referencing() -> LiteralValue {
+        let list = LiteralValue::list(vec![LiteralValue::Number(1.0)]);
+        if let LiteralValue::List(items) = &list {
+            items.borrow_mut().push(list.clone());
+        }
+        list
+    }
+
+    fn break_cycle(value: &LiteralValue) {
+        if let LiteralValue::List(items) = value {
+            items.borrow_mut().clear();
+        }
+    }
+
+    #[test]
+    fn cyclic_values_print_and_compare_without_recursing_forever() {
+        let (a, b) = (self_referencing(), self_referencing());
+        assert_eq!(a.to_string(), "[1, [...]]");
+        let map = LiteralValue::map(BTreeMap::from([("self".to_string(), LiteralValue::Nil)]));
+        if let LiteralValue::Map(entries) = &map {
+            entries.borrow_mut().insert("self".to_string(), map.clone());
+            entries.borrow_mut().insert("xs".to_string(), a.clone());
+        }
+        assert_eq!(map.to_string(), r#"{"self": {...}, "xs": [1, [...]]}"#);
+        // a repeat that isn't a cycle prints in full
+        let shared = LiteralValue::list(vec![LiteralValue::Nil]);
+        let pair = LiteralValue::list(vec![shared.clone(), shared]);
+        assert_eq!(pair.to_string(), "[[nil], [nil]]");
+
+        assert_eq!(a, a.clone());
+        assert_eq!(a, b);
+        let c = LiteralValue::list(vec![LiteralValue::Number(2.0), b.clone()]);
+        assert_ne!(a, c);
+        if let LiteralValue::Map(entries) = &map {
+            entries.borrow_mut().clear();
+        }
+        for value in [a, b] {
+            break_cycle(&value);
+        }
+    }
 }
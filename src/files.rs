@@ -0,0 +1,374 @@
+use std::{
+    cell::RefCell,
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Read, Write},
+    path::Path,
+    rc::{Rc, Weak},
+};
+
+use crate::{
+    builtins::{Builtin, native, string},
+    environment::Environment,
+    error::RuntimeError,
+    expr::{Arity, LiteralValue},
+};
+
+type EnvRef = Rc<RefCell<Environment>>;
+type Method = fn(&FileHandle, &[LiteralValue]) -> Result<LiteralValue, String>;
+
+enum Handle {
+    Reader(BufReader<File>),
+    Writer(BufWriter<File>),
+    Closed,
+}
+
+thread_local! {
+    // handles opened for writing, so `flush_open` can save them at exit even
+    // when a reference cycle keeps a handle from being dropped
+    static WRITERS: RefCell<Vec<Weak<FileHandle>>> = const { RefCell::new(Vec::new()) };
+}
+
+// flushes every writer that is still open, returning the errors
+pub fn flush_open() -> Vec<String> {
+    let writers: Vec<_> = WRITERS.with_borrow_mut(std::mem::take);
+    writers
+        .iter()
+        .filter_map(Weak::upgrade)
+        .filter_map(|file| match &mut *file.handle.borrow_mut() {
+            Handle::Writer(writer) => writer
+                .flush()
+                .err()
+                .map(|e| format!("Could not write {}: {}", file.path, e)),
+            _ => None,
+        })
+        .collect()
+}
+
+// what `fs.open` returns; writes are buffered until flush, close or the
+// handle is dropped
+pub struct FileHandle {
+    pub path: String,
+    handle: RefCell<Handle>,
+}
+
+impl FileHandle {
+    // the next line without its line ending, None at the end of the file
+    pub fn read_line(&self) -> Result<Option<String>, String> {
+        let mut line = String::new();
+        let read = self
+            .with_reader(|r| r.read_line(&mut line))
+            .map_err(|e| format!("Could not read {}: {}", self.path, e))?;
+        if read == 0 {
+            return Ok(None);
+        }
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        Ok(Some(line))
+    }
+
+    fn with_reader<T>(
+        &self,
+        f: impl FnOnce(&mut BufReader<File>) -> std::io::Result<T>,
+    ) -> Result<T, std::io::Error> {
+        match &mut *self.handle.borrow_mut() {
+            Handle::Reader(reader) => f(reader),
+            Handle::Writer(_) => Err(std::io::Error::other("file is not open for reading")),
+            Handle::Closed => Err(std::io::Error::other("file is closed")),
+        }
+    }
+
+    fn with_writer<T>(
+        &self,
+        f: impl FnOnce(&mut BufWriter<File>) -> std::io::Result<T>,
+    ) -> Result<T, String> {
+        let result = match &mut *self.handle.borrow_mut() {
+            Handle::Writer(writer) => f(writer),
+            Handle::Reader(_) => Err(std::io::Error::other("file is not open for writing")),
+            Handle::Closed => Err(std::io::Error::other("file is closed")),
+        };
+        result.map_err(|e| format!("Could not write {}: {}", self.path, e))
+    }
+}
+
+pub fn module() -> Environment {
+    let mut env = Environment::new();
+    let functions: [(&str, Arity, Builtin); 10] = [
+        ("read_file", Arity::exact(1), read_file),
+        ("write_file", Arity::exact(2), write_file),
+        ("append_file", Arity::exact(2), append_file),
+        ("read_lines", Arity::exact(1), read_lines),
+        ("exists", Arity::exact(1), exists),
+        ("list_dir", Arity::between(0, 1), list_dir),
+        ("mkdir", Arity::exact(1), mkdir),
+        ("remove", Arity::exact(1), remove),
+        ("open", Arity::between(1, 2), open),
+        ("is_dir", Arity::exact(1), is_dir),
+    ];
+    for (name, arity, fun) in functions {
+        env.define_const(name, native(name, arity, fun));
+    }
+    env
+}
+
+fn read_file(_env: EnvRef, args: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let path = string("read_file", &args[0])?;
+    fs::read_to_string(path)
+        .map(LiteralValue::StringValue)
+        .map_err(|e| format!("Could not read {}: {}", path, e).into())
+}
+
+fn write_file(_env: EnvRef, args: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let path = string("write_file", &args[0])?;
+    let text = string("write_file", &args[1])?;
+    fs::write(path, text).map_err(|e| format!("Could not write {}: {}", path, e))?;
+    Ok(LiteralValue::Nil)
+}
+
+fn append_file(_env: EnvRef, args: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let path = string("append_file", &args[0])?;
+    let text = string("append_file", &args[1])?;
+    OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(text.as_bytes()))
+        .map_err(|e| format!("Could not write {}: {}", path, e))?;
+    Ok(LiteralValue::Nil)
+}
+
+fn read_lines(_env: EnvRef, args: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let path = string("read_lines", &args[0])?;
+    let text = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+    Ok(LiteralValue::list(
+        text.lines()
+            .map(|line| LiteralValue::StringValue(line.to_string()))
+            .collect(),
+    ))
+}
+
+fn exists(_env: EnvRef, args: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let path = string("exists", &args[0])?;
+    Ok(LiteralValue::from_bool(Path::new(path).exists()))
+}
+
+fn is_dir(_env: EnvRef, args: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let path = string("is_dir", &args[0])?;
+    Ok(LiteralValue::from_bool(Path::new(path).is_dir()))
+}
+
+// entry names, sorted; the working directory when no path is given
+fn list_dir(_env: EnvRef, args: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let path = match args.first() {
+        Some(path) => string("list_dir", path)?,
+        None => ".",
+    };
+    let error = |e: std::io::Error| format!("Could not list {}: {}", path, e);
+    let mut names = vec![];
+    for entry in fs::read_dir(path).map_err(error)? {
+        let entry = entry.map_err(error)?;
+        names.push(entry.file_name().to_string_lossy().into_owned());
+    }
+    names.sort();
+    Ok(LiteralValue::list(
+        names.into_iter().map(LiteralValue::StringValue).collect(),
+    ))
+}
+
+// creates missing parents too, and is fine if the directory already exists
+fn mkdir(_env: EnvRef, args: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let path = string("mkdir", &args[0])?;
+    fs::create_dir_all(path).map_err(|e| format!("Could not create {}: {}", path, e))?;
+    Ok(LiteralValue::Nil)
+}
+
+// a file or an empty directory
+fn remove(_env: EnvRef, args: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let path = string("remove", &args[0])?;
+    let result = if Path::new(path).is_dir() {
+        fs::remove_dir(path)
+    } else {
+        fs::remove_file(path)
+    };
+    result.map_err(|e| format!("Could not remove {}: {}", path, e))?;
+    Ok(LiteralValue::Nil)
+}
+
+// open(path, mode) with mode "r" (the default), "w" or "a"
+fn open(_env: EnvRef, args: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let path = string("open", &args[0])?;
+    let mode = match args.get(1) {
+        Some(mode) => string("open", mode)?,
+        None => "r",
+    };
+    let error = |e: std::io::Error| format!("Could not open {}: {}", path, e);
+    let handle = match mode {
+        "r" => Handle::Reader(BufReader::new(File::open(path).map_err(error)?)),
+        "w" => Handle::Writer(BufWriter::new(File::create(path).map_err(error)?)),
+        "a" => Handle::Writer(BufWriter::new(
+            OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(path)
+                .map_err(error)?,
+        )),
+        other => return Err(format!("Unknown file mode {:?}", other).into()),
+    };
+    let writer = matches!(handle, Handle::Writer(_));
+    let file = Rc::new(FileHandle {
+        path: path.to_string(),
+        handle: RefCell::new(handle),
+    });
+    if writer {
+        WRITERS.with_borrow_mut(|writers| {
+            writers.retain(|w| w.strong_count() > 0);
+            writers.push(Rc::downgrade(&file));
+        });
+    }
+    Ok(LiteralValue::File(file))
+}
+
+// `file.read_line()` and friends, bound to the handle like string methods
+pub fn method(file: &Rc<FileHandle>, name: &str) -> Option<LiteralValue> {
+    let (arity, fun): (Arity, Method) = match name {
+        "read_line" => (Arity::exact(0), |file, _| {
+            Ok(file
+                .read_line()?
+                .map(LiteralValue::StringValue)
+                .unwrap_or(LiteralValue::Nil))
+        }),
+        "lines" => (Arity::exact(0), |file, _| {
+            let mut lines = vec![];
+            while let Some(line) = file.read_line()? {
+                lines.push(LiteralValue::StringValue(line));
+            }
+            Ok(LiteralValue::list(lines))
+        }),
+        "read" => (Arity::exact(0), |file, _| {
+            let mut text = String::new();
+            file.with_reader(|r| r.read_to_string(&mut text))
+                .map_err(|e| format!("Could not read {}: {}", file.path, e))?;
+            Ok(LiteralValue::StringValue(text))
+        }),
+        "write" => (Arity::exact(1), |file, args| {
+            let text = string("write", &args[0])?;
+            file.with_writer(|w| w.write_all(text.as_bytes()))?;
+            Ok(LiteralValue::Nil)
+        }),
+        "flush" => (Arity::exact(0), |file, _| {
+            file.with_writer(|w| w.flush())?;
+            Ok(LiteralValue::Nil)
+        }),
+        // closing twice is fine; buffered writes are flushed first
+        "close" => (Arity::exact(0), |file, _| {
+            let handle = file.handle.replace(Handle::Closed);
+            if let Handle::Writer(mut writer) = handle {
+                writer
+                    .flush()
+                    .map_err(|e| format!("Could not write {}: {}", file.path, e))?;
+            }
+            Ok(LiteralValue::Nil)
+        }),
+        _ => return None,
+    };
+    let file = file.clone();
+    Some(LiteralValue::Callable {
+        name: name.to_string(),
+        arity,
+        params: Rc::from([]),
+        keywords: Rc::from([]),
+        fun: Rc::new(move |_env, args| Ok(fun(&file, args)?)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env() -> EnvRef {
+        Rc::new(RefCell::new(Environment::new()))
+    }
+
+    fn text(s: &str) -> LiteralValue {
+        LiteralValue::StringValue(s.to_string())
+    }
+
+    fn open_file(path: &str, mode: &str) -> Rc<FileHandle> {
+        match open(env(), &[text(path), text(mode)]) {
+            Ok(LiteralValue::File(file)) => file,
+            other => panic!(
+                "open {} failed: {:?}",
+                path,
+                other.map_err(|e| e.to_string())
+            ),
+        }
+    }
+
+    fn call(file: &Rc<FileHandle>, name: &str, args: Vec<LiteralValue>) -> Result<String, String> {
+        method(file, name)
+            .unwrap()
+            .call(env(), args)
+            .map(|v| v.to_string())
+            .map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn handles_enforce_their_mode() {
+        let dir = std::env::temp_dir().join(format!("nox-files-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("crlf.txt").display().to_string();
+
+        let writer = open_file(&path, "w");
+        assert_eq!(
+            call(&writer, "write", vec![text("a\r\nb")]),
+            Ok("nil".into())
+        );
+        assert!(
+            call(&writer, "read_line", vec![])
+                .unwrap_err()
+                .contains("not open for reading")
+        );
+        assert_eq!(call(&writer, "close", vec![]), Ok("nil".into()));
+        assert_eq!(call(&writer, "close", vec![]), Ok("nil".into()));
+        assert!(
+            call(&writer, "write", vec![text("x")])
+                .unwrap_err()
+                .contains("file is closed")
+        );
+
+        let appender = open_file(&path, "a");
+        call(&appender, "write", vec![text("\n")]).unwrap();
+        // dropping the handle flushes it
+        drop(appender);
+
+        let reader = open_file(&path, "r");
+        assert_eq!(call(&reader, "read_line", vec![]), Ok("a".into()));
+        assert!(
+            call(&reader, "write", vec![text("x")])
+                .unwrap_err()
+                .contains("not open for writing")
+        );
+        assert_eq!(call(&reader, "read", vec![]), Ok("b\n".into()));
+        assert_eq!(call(&reader, "read_line", vec![]), Ok("nil".into()));
+
+        let unknown = open(env(), &[text(&path), text("rw")]).map_err(|e| e.to_string());
+        assert_eq!(
+            unknown.err(),
+            Some("[line 0] Unknown file mode \"rw\"".to_string())
+        );
+        // a handle something still holds is saved by flush_open
+        let kept = open_file(&path, "w");
+        call(&kept, "write", vec![text("kept")]).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "");
+        assert!(flush_open().is_empty());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "kept");
+        drop(kept);
+
+        // a directory with something in it is left alone
+        assert!(remove(env(), &[text(&dir.display().to_string())]).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,175 +1,1380 @@
-use std::{cell::RefCell, env, error::Error, ops::ControlFlow, rc::Rc, result, time::{Instant, SystemTime}};
+use std::{
+    cell::RefCell,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
 use crate::{
-    environment::{self, Environment}, expr::{self, Expr, LiteralValue}, parser, stmt::{self, Stmt}
+    builtins,
+    environment::Environment,
+    error::RuntimeError,
+    expr::{Arity, LiteralValue},
+    module::ModuleCache,
+    parser::Parser,
+    resolver::Resolver,
+    scanner::Scanner,
+    stmt::{Pattern, Stmt},
+    token::Token,
 };
 
+// (key, value) pairs produced by `for ... in`
+type Items = Box<dyn Iterator<Item = Result<(LiteralValue, LiteralValue), RuntimeError>>>;
+
 pub struct Interpreter {
     environment: Rc<RefCell<Environment>>,
+    modules: Rc<RefCell<ModuleCache>>,
+    script: Option<PathBuf>,
 }
 #[derive(PartialEq)]
 pub enum ControllFlow {
     None,
-    Break,
+    // jumps carry the label they target, `None` for the innermost loop
+    Break(Option<String>),
     ReturnVal(LiteralValue),
-    Continue,
-}
-pub fn time_fn(env:Rc<RefCell<Environment>>,args:&Vec<LiteralValue>)->LiteralValue{
-
-    let a = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
-    LiteralValue::Number(a.as_secs_f64())
+    Continue(Option<String>),
 }
-pub fn floor(env:Rc<RefCell<Environment>>,args:&Vec<LiteralValue>)->LiteralValue{
-    let value = &args[0];
-    if let LiteralValue::Number(x)= value{
 
-        LiteralValue::Number( x.floor() )
-    }else {
-        panic!("Expected number");
+impl ControllFlow {
+    // what a loop labelled `label` does with its body's result: Ok(true) runs
+    // the next iteration, Ok(false) ends the loop, and Err passes a return or
+    // a jump aimed at an outer loop on to the enclosing statement
+    fn in_loop(self, label: Option<&str>) -> Result<bool, ControllFlow> {
+        match self {
+            ControllFlow::None | ControllFlow::Continue(None) => Ok(true),
+            ControllFlow::Break(None) => Ok(false),
+            ControllFlow::Continue(Some(l)) if Some(l.as_str()) == label => Ok(true),
+            ControllFlow::Break(Some(l)) if Some(l.as_str()) == label => Ok(false),
+            other => Err(other),
+        }
     }
 }
 impl Interpreter {
-    fn forClosure(parent:Rc<RefCell<Environment>>) -> Self{
+    fn forClosure(
+        parent: Rc<RefCell<Environment>>,
+        modules: Rc<RefCell<ModuleCache>>,
+        script: Option<PathBuf>,
+    ) -> Self {
         let env = Rc::new(RefCell::new(Environment::new()));
         env.borrow_mut().enclosing = Some(parent);
-        Self { environment: env }
+        Self {
+            environment: env,
+            modules,
+            script,
+        }
     }
     pub fn new() -> Self {
-        let mut global = Environment::new();
-        global.define(&"time".to_string(), LiteralValue::Callable { name: "time".to_string(), arity: 0, fun:Rc::new(time_fn) });
- global.define(&"floor".to_string(), LiteralValue::Callable { name: "floor".to_string(), arity: 1, fun:Rc::new(floor) });
         Self {
-            environment: Rc::new(RefCell::new(global)),
+            environment: Rc::new(RefCell::new(Self::globals())),
+            modules: Rc::new(RefCell::new(ModuleCache::new())),
+            script: None,
+        }
+    }
+
+    // the file imports are resolved against
+    pub fn set_script(&mut self, path: &Path) {
+        self.script = path.canonicalize().ok();
+    }
+
+    // what `os.args()` returns
+    pub fn set_args(&mut self, args: Vec<String>) {
+        self.modules.borrow_mut().args = args;
+    }
+
+    // calls a global function with no arguments, as the test runner does
+    pub fn call_function(&mut self, name: &str) -> Result<LiteralValue, RuntimeError> {
+        let function =
+            self.environment.borrow().get(name).ok_or_else(|| {
+                RuntimeError::new(0, format!("Variable {} is not declared", name))
+            })?;
+        function.call(self.environment.clone(), vec![])
+    }
+
+    // compile-time checks against the names this interpreter already knows;
+    // returns the warnings
+    pub fn resolve(&self, statements: &[Stmt]) -> Result<Vec<String>, String> {
+        Resolver::new(&self.environment.borrow()).resolve(statements)
+    }
+
+    fn globals() -> Environment {
+        let mut global = Environment::new();
+        builtins::define_globals(&mut global);
+        global
+    }
+
+    fn import_module(
+        &mut self,
+        keyword: &Token,
+        spec: &str,
+    ) -> Result<Rc<RefCell<Environment>>, RuntimeError> {
+        if let Some(module) = self.modules.borrow_mut().native(spec) {
+            return Ok(module);
+        }
+        let path = self
+            .modules
+            .borrow()
+            .resolve(self.script.as_deref(), spec)
+            .map_err(|e| RuntimeError::new(keyword.line, e))?;
+        if let Some(module) = self.modules.borrow().get(&path) {
+            return Ok(module);
         }
+        self.modules
+            .borrow_mut()
+            .begin(&path)
+            .map_err(|e| RuntimeError::new(keyword.line, e))?;
+        let result = self.run_module(keyword, &path);
+        self.modules
+            .borrow_mut()
+            .finish(&path, result.as_ref().ok().cloned());
+        result
     }
 
-    pub fn interpret(&mut self, expr: Expr) -> Result<LiteralValue, String> {
-        expr.eval(self.environment.clone())
+    // each module gets its own globals on top of a fresh set of builtins, so
+    // its namespace only holds what the file itself defines
+    fn run_module(
+        &self,
+        keyword: &Token,
+        path: &Path,
+    ) -> Result<Rc<RefCell<Environment>>, RuntimeError> {
+        let source = fs::read_to_string(path).map_err(|e| {
+            RuntimeError::new(
+                keyword.line,
+                format!("Could not read {}: {}", path.display(), e),
+            )
+        })?;
+        let tokens = Scanner::new(source).scanTokens();
+        let mut parser = Parser::new(tokens);
+        let statements = parser.parse().map_err(|e| {
+            RuntimeError::new(keyword.line, format!("In {}: {}", path.display(), e))
+        })?;
+        let mut module = Interpreter::forClosure(
+            Rc::new(RefCell::new(Self::globals())),
+            self.modules.clone(),
+            Some(path.to_path_buf()),
+        );
+        let warnings = module.resolve(&statements).map_err(|e| {
+            RuntimeError::new(keyword.line, format!("In {}: {}", path.display(), e))
+        })?;
+        for warning in parser.warnings.iter().chain(&warnings) {
+            eprintln!("In {}: {}", path.display(), warning);
+        }
+        module.interpret_stmt(&statements)?;
+        Ok(module.environment)
     }
-    #[allow(warnings)]
-    pub fn interpret_stmt(&mut self, st: &[Stmt]) -> Result<ControllFlow, String> {
+
+    // binds a name in the current scope unless a constant (or builtin)
+    // already owns it
+    fn declare(
+        &self,
+        name: &Token,
+        value: LiteralValue,
+        constant: bool,
+    ) -> Result<(), RuntimeError> {
+        let mut env = self.environment.borrow_mut();
+        if env.is_constant(&name.lexeme) {
+            return Err(RuntimeError::new(
+                name.line,
+                format!("Cannot redefine constant {}", name.lexeme),
+            ));
+        }
+        if constant {
+            env.define_const(&name.lexeme, value);
+        } else {
+            env.define(&name.lexeme, value);
+        }
+        Ok(())
+    }
+
+    // runs `f` inside a fresh child scope, restoring the old scope even when
+    // `f` fails so a caught error doesn't leave us in the wrong environment
+    fn in_scope<F>(&mut self, f: F) -> Result<ControllFlow, RuntimeError>
+    where
+        F: FnOnce(&mut Self) -> Result<ControllFlow, RuntimeError>,
+    {
+        let mut new_env = Environment::new();
+        new_env.enclosing = Some(self.environment.clone());
+        let old_env = std::mem::replace(&mut self.environment, Rc::new(new_env.into()));
+        let result = f(self);
+        self.environment = old_env;
+        result
+    }
+
+    // a single loop variable gets the key for maps and the value for
+    // everything else, so the flag says which half of the pair to bind
+    fn iterate(
+        value: LiteralValue,
+        env: Rc<RefCell<Environment>>,
+    ) -> Result<(Items, bool), RuntimeError> {
+        let index = |i: usize| LiteralValue::Number(i as f64);
+        // `{iter: f}` hands out a fresh iterator, `{next: f}` is one
+        let member = |value: &LiteralValue, name: &str| match value {
+            LiteralValue::Map(entries) => entries
+                .borrow()
+                .get(name)
+                .filter(|f| matches!(f, LiteralValue::Callable { .. }))
+                .cloned(),
+            _ => None,
+        };
+        let value = match member(&value, "iter") {
+            Some(iter) => iter.call(env.clone(), vec![])?,
+            None => value,
+        };
+        if let Some(next) = member(&value, "next") {
+            let mut count = 0;
+            let mut done = false;
+            let items = std::iter::from_fn(move || {
+                if done {
+                    return None;
+                }
+                match next.call(env.clone(), vec![]) {
+                    Ok(LiteralValue::Nil) => {
+                        done = true;
+                        None
+                    }
+                    Ok(value) => {
+                        count += 1;
+                        Some(Ok((index(count - 1), value)))
+                    }
+                    Err(e) => {
+                        done = true;
+                        Some(Err(e))
+                    }
+                }
+            });
+            return Ok((Box::new(items), false));
+        }
+        match value {
+            // reads the list as it goes, so items appended in the body are seen
+            LiteralValue::List(items) => {
+                let items = (0..).map_while(move |i| {
+                    let item = items.borrow().get(i).cloned();
+                    item.map(|v| Ok((index(i), v)))
+                });
+                Ok((Box::new(items), false))
+            }
+            LiteralValue::Map(entries) => {
+                let entries: Vec<_> = entries
+                    .borrow()
+                    .iter()
+                    .map(|(k, v)| Ok((LiteralValue::StringValue(k.clone()), v.clone())))
+                    .collect();
+                Ok((Box::new(entries.into_iter()), true))
+            }
+            // lines are read as the loop asks for them
+            LiteralValue::File(file) => {
+                let mut count = 0;
+                let lines = std::iter::from_fn(move || match file.read_line() {
+                    Ok(Some(line)) => {
+                        count += 1;
+                        Some(Ok((index(count - 1), LiteralValue::StringValue(line))))
+                    }
+                    Ok(None) => None,
+                    Err(e) => Some(Err(e.into())),
+                });
+                Ok((Box::new(lines.fuse()), false))
+            }
+            LiteralValue::StringValue(s) => {
+                let chars: Vec<_> = s
+                    .chars()
+                    .enumerate()
+                    .map(|(i, c)| Ok((index(i), LiteralValue::StringValue(c.to_string()))))
+                    .collect();
+                Ok((Box::new(chars.into_iter()), false))
+            }
+            LiteralValue::Range { start, step, .. } => {
+                let items = (0..value.range_len())
+                    .map(move |i| Ok((index(i), LiteralValue::Number(start + i as f64 * step))));
+                Ok((Box::new(items), false))
+            }
+            other => Err(format!("{} is not iterable", other.to_type()).into()),
+        }
+    }
+
+    // collects the names a pattern binds; bindings from a failed match are
+    // thrown away by the caller
+    fn match_pattern(
+        pattern: &Pattern,
+        value: &LiteralValue,
+        bindings: &mut Vec<(String, LiteralValue)>,
+    ) -> bool {
+        match pattern {
+            Pattern::Wildcard => true,
+            Pattern::Literal(literal) => literal == value,
+            Pattern::Binding(name) => {
+                bindings.push((name.lexeme.clone(), value.clone()));
+                true
+            }
+            Pattern::Alternatives(alts) => {
+                alts.iter().any(|p| Self::match_pattern(p, value, bindings))
+            }
+            Pattern::List { items, rest } => {
+                let LiteralValue::List(values) = value else {
+                    return false;
+                };
+                let values = values.borrow();
+                let fits = match rest {
+                    Some(_) => values.len() >= items.len(),
+                    None => values.len() == items.len(),
+                };
+                if !fits
+                    || !items
+                        .iter()
+                        .zip(values.iter())
+                        .all(|(p, v)| Self::match_pattern(p, v, bindings))
+                {
+                    return false;
+                }
+                if let Some(Some(name)) = rest {
+                    let tail = values[items.len()..].to_vec();
+                    bindings.push((name.lexeme.clone(), LiteralValue::list(tail)));
+                }
+                true
+            }
+            Pattern::Map(entries) => {
+                let LiteralValue::Map(map) = value else {
+                    return false;
+                };
+                let map = map.borrow();
+                entries.iter().all(|(key, p)| {
+                    map.get(key)
+                        .is_some_and(|v| Self::match_pattern(p, v, bindings))
+                })
+            }
+        }
+    }
+
+    pub fn interpret_stmt(&mut self, st: &[Stmt]) -> Result<ControllFlow, RuntimeError> {
         for i in st {
             match i {
-                Stmt::Return { token, expr }=>{
-                   if let Some(value) = expr{
-                        return Ok(ControllFlow::ReturnVal(value.eval(self.environment.clone())?));
-                   }else {
+                Stmt::Return { expr, .. } => {
+                    if let Some(value) = expr {
+                        return Ok(ControllFlow::ReturnVal(
+                            value.eval(self.environment.clone())?,
+                        ));
+                    } else {
                         return Ok(ControllFlow::ReturnVal(LiteralValue::Nil));
-                   }
+                    }
                 }
-                Stmt::Function { name, params, body }=>{
-
-                    let arity = params.len();
+                Stmt::Function {
+                    name,
+                    params,
+                    rest,
+                    body,
+                } => {
+                    let required = params.iter().filter(|p| p.default.is_none()).count();
+                    let arity = match rest {
+                        Some(_) => Arity::at_least(required),
+                        None => Arity::between(required, params.len()),
+                    };
+                    let names = params.iter().map(|p| p.name.lexeme.clone()).collect();
                     let params = params.clone();
+                    let rest = rest.clone();
                     let body = body.clone();
-                    let call = move |parent,args:&Vec<LiteralValue>| {
-                        let mut closure_interpreter = Interpreter::forClosure(parent);
-                        for (i,arg) in args.iter().enumerate(){
-                            closure_interpreter.environment.borrow_mut().define(&params.clone()[i].lexeme, arg.clone());
+                    let closure = self.environment.clone();
+                    let modules = self.modules.clone();
+                    let script = self.script.clone();
+                    let call = move |_, args: &[LiteralValue]| {
+                        let mut closure_interpreter = Interpreter::forClosure(
+                            closure.clone(),
+                            modules.clone(),
+                            script.clone(),
+                        );
+                        let env = closure_interpreter.environment.clone();
+                        // defaults are evaluated per call and can use earlier params
+                        for (i, param) in params.iter().enumerate() {
+                            let value = match (args.get(i), &param.default) {
+                                (None | Some(LiteralValue::Nil), Some(default)) => {
+                                    default.eval(env.clone())?
+                                }
+                                (arg, _) => arg.cloned().unwrap_or(LiteralValue::Nil),
+                            };
+                            env.borrow_mut().define(&param.name.lexeme, value);
+                        }
+                        if let Some(rest) = &rest {
+                            let extra = args.get(params.len()..).unwrap_or_default().to_vec();
+                            env.borrow_mut()
+                                .define(&rest.lexeme, LiteralValue::list(extra));
                         }
-;
-                        let mut cf =closure_interpreter.interpret_stmt(&body).unwrap();
-                        let return_value;
-                        if let ControllFlow::ReturnVal(val) = cf{
-                            return_value = val; 
-                        }else {
-                            return_value = LiteralValue::Nil;
+                        match closure_interpreter.interpret_stmt(&body)? {
+                            ControllFlow::ReturnVal(val) => Ok(val),
+                            _ => Ok(LiteralValue::Nil),
                         }
-                        return_value
                     };
 
-                    let callable = LiteralValue::Callable { name:name.to_string(), arity, fun: Rc::new(call)};
-                    self.environment.borrow_mut().define(&name.lexeme, callable);
-
-                },
-                Stmt::Break => return Ok(ControllFlow::Break),
-                Stmt::Continue => return Ok(ControllFlow::Continue),
-                Stmt::WHILE { condition, block } => match **block {
-                    Stmt::Block { ref stmts } => {
-                        let mut new_env = Environment::new();
-                        new_env.enclosing = Some(self.environment.clone());
-                        let old_env = self.environment.clone();
-                        self.environment = Rc::new(new_env.into());
-                        'nox_loop: while condition.eval(self.environment.clone())?.is_truthy() {
-                            match self.interpret_stmt(&stmts)?{
-                               ControllFlow::Break=>break 'nox_loop,
-                               ControllFlow::Continue=> {
-                                    let a = stmts.last().unwrap();
-                                    self.interpret_stmt(&[a.clone()]);
-                                    continue 'nox_loop
-                                },
-                               _=>(),
+                    let callable = LiteralValue::Callable {
+                        name: name.lexeme.clone(),
+                        arity,
+                        params: names,
+                        keywords: Rc::from([]),
+                        fun: Rc::new(call),
+                    };
+                    self.declare(name, callable, false)?;
+                }
+                Stmt::Import {
+                    keyword,
+                    path,
+                    alias,
+                } => {
+                    let module = self.import_module(keyword, path)?;
+                    let value = LiteralValue::Module {
+                        name: alias.lexeme.clone(),
+                        env: module,
+                    };
+                    self.declare(alias, value, false)?;
+                }
+                Stmt::FromImport {
+                    keyword,
+                    path,
+                    names,
+                } => {
+                    let module = self.import_module(keyword, path)?;
+                    for name in names {
+                        let Some(value) = module.borrow().values.get(&name.lexeme).cloned() else {
+                            return Err(RuntimeError::new(
+                                name.line,
+                                format!("Module {} has no member {}", path, name.lexeme),
+                            ));
+                        };
+                        self.declare(name, value, false)?;
+                    }
+                }
+                Stmt::Break { label } => return Ok(ControllFlow::Break(label.clone())),
+                Stmt::Continue { label } => return Ok(ControllFlow::Continue(label.clone())),
+                Stmt::Throw { keyword, value } => {
+                    return Err(RuntimeError::Thrown {
+                        value: value.eval(self.environment.clone())?,
+                        line: keyword.line,
+                    });
+                }
+                Stmt::Try {
+                    body,
+                    catch,
+                    finally,
+                } => {
+                    let mut result = self.in_scope(|this| this.interpret_stmt(body));
+                    if let Err(RuntimeError::Exit { .. }) = result {
+                        return result;
+                    }
+                    if let (Err(_), Some((name, handler))) = (&result, catch) {
+                        let error = result.err().unwrap().into_value();
+                        result = self.in_scope(|this| {
+                            this.environment.borrow_mut().define(&name.lexeme, error);
+                            this.interpret_stmt(handler)
+                        });
+                    }
+                    if let Some(finally) = finally {
+                        let cf = self.in_scope(|this| this.interpret_stmt(finally))?;
+                        if cf != ControllFlow::None {
+                            return Ok(cf);
+                        }
+                    }
+                    let cf = result?;
+                    if cf != ControllFlow::None {
+                        return Ok(cf);
+                    }
+                }
+                Stmt::ForIn {
+                    names,
+                    keyword,
+                    iterable,
+                    body,
+                    label,
+                } => {
+                    let value = iterable.eval(self.environment.clone())?;
+                    let (items, keys_first) = Self::iterate(value, self.environment.clone())
+                        .map_err(|e| e.with_line(keyword.line))?;
+                    for item in items {
+                        let (key, value) = item.map_err(|e| e.with_line(keyword.line))?;
+                        let cf = self.in_scope(|this| {
+                            {
+                                let mut env = this.environment.borrow_mut();
+                                match names.as_slice() {
+                                    [name] if keys_first => env.define(&name.lexeme, key),
+                                    [name] => env.define(&name.lexeme, value),
+                                    [k, v, ..] => {
+                                        env.define(&k.lexeme, key);
+                                        env.define(&v.lexeme, value);
+                                    }
+                                    [] => (),
+                                }
+                            }
+                            this.interpret_stmt(std::slice::from_ref(&**body))
+                        })?;
+                        match cf.in_loop(label.as_deref()) {
+                            Ok(true) => (),
+                            Ok(false) => break,
+                            Err(cf) => return Ok(cf),
+                        }
+                    }
+                }
+                Stmt::Match { value, arms } => {
+                    let value = value.eval(self.environment.clone())?;
+                    for arm in arms {
+                        let mut bindings = vec![];
+                        if !Self::match_pattern(&arm.pattern, &value, &mut bindings) {
+                            continue;
+                        }
+                        let mut guard_failed = false;
+                        let cf = self.in_scope(|this| {
+                            for (name, value) in bindings {
+                                this.environment.borrow_mut().define(&name, value);
+                            }
+                            if let Some(guard) = &arm.guard
+                                && !guard.eval(this.environment.clone())?.is_truthy()
+                            {
+                                guard_failed = true;
+                                return Ok(ControllFlow::None);
                             }
+                            this.interpret_stmt(std::slice::from_ref(&arm.body))
+                        })?;
+                        if guard_failed {
+                            continue;
                         }
-                        self.environment = old_env;
+                        if cf != ControllFlow::None {
+                            return Ok(cf);
+                        }
+                        break;
+                    }
+                }
+                Stmt::WHILE {
+                    condition,
+                    block,
+                    increment,
+                    label,
+                } => {
+                    'nox_loop: while condition.eval(self.environment.clone())?.is_truthy() {
+                        let cf = self.interpret_stmt(std::slice::from_ref(&**block))?;
+                        match cf.in_loop(label.as_deref()) {
+                            Ok(true) => (),
+                            Ok(false) => break 'nox_loop,
+                            Err(cf) => return Ok(cf),
+                        }
+                        if let Some(increment) = increment {
+                            increment.eval(self.environment.clone())?;
+                        }
+                    }
+                }
+                Stmt::DoWhile {
+                    body,
+                    condition,
+                    label,
+                } => loop {
+                    let cf = self.interpret_stmt(std::slice::from_ref(&**body))?;
+                    match cf.in_loop(label.as_deref()) {
+                        Ok(true) => (),
+                        Ok(false) => break,
+                        Err(cf) => return Ok(cf),
+                    }
+                    if !condition.eval(self.environment.clone())?.is_truthy() {
+                        break;
                     }
-                    _ => return Err("Invalid expr".to_string()),
                 },
                 Stmt::IfElse {
                     condition,
                     then,
                     els,
                 } => {
-                    let a = condition.eval(self.environment.clone())?;
-                    if a == LiteralValue::True {
-                        match **then {
-                            Stmt::Block { ref stmts } => {
-                                let mut new_env = Environment::new();
-                                new_env.enclosing = Some(self.environment.clone());
-                                let old_env = self.environment.clone();
-                                self.environment = Rc::new(new_env.into());
-                                let cf =self.interpret_stmt(&stmts)?;
-                                self.environment = old_env;
-                                if cf != ControllFlow::None {
-                                   return Ok(cf);
-                                }
-                            }
-                            _ => {
-                                return Err("invalid Expr".to_string());
-                            }
-                        }
+                    let branch = if condition.eval(self.environment.clone())?.is_truthy() {
+                        Some(then)
                     } else {
-                        if let Some(a) = els {
-                            if let Stmt::Block { ref stmts } = **a {
-                                let mut new_env = Environment::new();
-                                new_env.enclosing = Some(self.environment.clone());
-
-                                let old_env = self.environment.clone();
-                                self.environment = Rc::new(new_env.into());
-                                let cf = self.interpret_stmt(&stmts)?;
-                                self.environment = old_env;
-                                if cf != ControllFlow::None{
-                                    return Ok(cf);
-                                }
-                            }
+                        els.as_ref()
+                    };
+                    if let Some(branch) = branch {
+                        let cf = self.interpret_stmt(std::slice::from_ref(&**branch))?;
+                        if cf != ControllFlow::None {
+                            return Ok(cf);
                         }
                     }
                 }
                 Stmt::Block { stmts } => {
-                    let mut new_env = Environment::new();
-                    new_env.enclosing = Some(self.environment.clone());
-
-                    let old_env = self.environment.clone();
-                    self.environment = Rc::new(new_env.into());
-                    let cf =self.interpret_stmt(stmts)?;
-                    self.environment = old_env;
+                    let cf = self.in_scope(|this| this.interpret_stmt(stmts))?;
                     if cf != ControllFlow::None {
-                         return Ok(cf);
+                        return Ok(cf);
                     }
                 }
                 Stmt::Expression { expression } => {
                     expression.eval(self.environment.clone())?;
                 }
-                Stmt::Print { expression } => {
-                    let value = expression.eval(self.environment.clone())?;
-                    println!("{}", value.to_string());
-                }
-                Stmt::Var { name, initializer } => {
+                Stmt::Var {
+                    name,
+                    initializer,
+                    constant,
+                } => {
                     let value = initializer.eval(self.environment.clone())?;
-                    self.environment.borrow_mut().define(&name.lexeme, value);
+                    self.declare(name, value, *constant)?;
                 }
             };
         }
         Ok(ControllFlow::None)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(source: &str) -> Interpreter {
+        let tokens = Scanner::new(source.to_string()).scanTokens();
+        let statements = Parser::new(tokens).parse().unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret_stmt(&statements).unwrap();
+        interpreter
+    }
+
+    fn global(interpreter: &Interpreter, name: &str) -> LiteralValue {
+        interpreter.environment.borrow().get(name).unwrap()
+    }
+
+    #[test]
+    fn modules_resolve_relative_to_the_importer_and_run_once() {
+        let dir = std::env::temp_dir().join(format!("nox-modules-{}", std::process::id()));
+        let file = |name: &str, source: &str| {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, source).unwrap();
+        };
+        // lib/my_mod.nox finds helper.nox next to itself, not next to main.nox
+        file("lib/helper.nox", "fun twice(x) { return x * 2; }");
+        file(
+            "lib/my_mod.nox",
+            "import \"helper.nox\";
+            var runs = 0;
+            runs++;
+            fun bump() { runs++; return runs; }
+            var four = helper.twice(2);",
+        );
+        file("cycle_a.nox", "import \"cycle_b.nox\";");
+        file("cycle_b.nox", "import \"cycle_a.nox\";");
+        file(
+            "main.nox",
+            "import \"lib/my_mod.nox\";
+            import \"lib/my_mod.nox\" as again;
+            from \"lib/my_mod.nox\" import four, bump;
+            var bumped = [my_mod.bump(), again.bump(), bump()];
+            var errors = [nil, nil, nil];
+            try { import \"cycle_a.nox\"; } catch (e) { errors[0] = e.message; }
+            try { import \"missing.nox\"; } catch (e) { errors[1] = e.message; }
+            try { from \"lib/helper.nox\" import nope; } catch (e) { errors[2] = e.message; }",
+        );
+
+        let path = dir.join("main.nox");
+        let source = fs::read_to_string(&path).unwrap();
+        let statements = Parser::new(Scanner::new(source).scanTokens())
+            .parse()
+            .unwrap();
+        let mut i = Interpreter::new();
+        i.set_script(&path);
+        let result = i.interpret_stmt(&statements);
+        let root = dir.canonicalize().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(result.is_ok());
+
+        // all three imports share one run of the module
+        assert_eq!(global(&i, "four"), LiteralValue::Number(4.0));
+        assert_eq!(global(&i, "bumped").to_string(), "[2, 3, 4]");
+        let a = root.join("cycle_a.nox").display().to_string();
+        let b = root.join("cycle_b.nox").display().to_string();
+        assert_eq!(
+            global(&i, "errors").to_string(),
+            format!(
+                "[\"Import cycle detected: {a} -> {b} -> {a}\", \
+                 \"Module missing.nox not found\", \
+                 \"Module lib/helper.nox has no member nope\"]"
+            )
+        );
+    }
+
+    #[test]
+    fn import_paths_are_string_literals() {
+        let tokens = Scanner::new(r#"import "dir\\my mod.nox" as m;"#.to_string()).scanTokens();
+        let statements = Parser::new(tokens).parse().unwrap();
+        assert!(matches!(
+            &statements[..],
+            [Stmt::Import { path, .. }] if path == "dir\\my mod.nox"
+        ));
+        let tokens = Scanner::new(r#"import "lib/while.nox";"#.to_string()).scanTokens();
+        assert!(Parser::new(tokens).parse().is_err());
+    }
+
+    #[test]
+    fn continue_in_for_runs_increment() {
+        let i = run("var s = 0;
+            for (var i = 0; i < 5; i = i + 1) {
+                if (i == 2) { continue; }
+                s = s + i;
+            }");
+        assert_eq!(global(&i, "s"), LiteralValue::Number(8.0));
+    }
+
+    #[test]
+    fn continue_in_while_skips_rest_of_body() {
+        let i = run("var i = 0; var s = 0; var last = 0;
+            while (i < 5) {
+                i = i + 1;
+                if (i == 3) { continue; }
+                s = s + i;
+                last = i;
+            }");
+        assert_eq!(global(&i, "s"), LiteralValue::Number(12.0));
+        assert_eq!(global(&i, "last"), LiteralValue::Number(5.0));
+    }
+
+    #[test]
+    fn continue_as_last_statement() {
+        let i = run("var n = 0;
+            for (var i = 0; i < 3; i = i + 1) { n = n + 1; continue; }");
+        assert_eq!(global(&i, "n"), LiteralValue::Number(3.0));
+    }
+
+    #[test]
+    fn nested_break_and_continue_affect_innermost_loop() {
+        let i = run("var pairs = 0; var outer = 0;
+            for (var a = 0; a < 4; a = a + 1) {
+                outer = outer + 1;
+                for (var b = 0; b < 4; b = b + 1) {
+                    if (b == 1) { continue; }
+                    if (b == 3) { break; }
+                    pairs = pairs + 1;
+                }
+                if (a == 2) { break; }
+            }");
+        assert_eq!(global(&i, "pairs"), LiteralValue::Number(6.0));
+        assert_eq!(global(&i, "outer"), LiteralValue::Number(3.0));
+    }
+
+    #[test]
+    fn single_statement_bodies() {
+        let i = run("fun sign(x) {
+                if (x < 0) return -1;
+                else if (x == 0) return 0;
+                else return 1;
+            }
+            var a = sign(-5); var b = sign(0); var c = sign(3);
+            var n = 0;
+            while (n < 3) n = n + 1;
+            var s = 0;
+            for (var i = 0; i < 4; i = i + 1) if (i != 2) s = s + i;");
+        assert_eq!(global(&i, "a"), LiteralValue::Number(-1.0));
+        assert_eq!(global(&i, "b"), LiteralValue::Number(0.0));
+        assert_eq!(global(&i, "c"), LiteralValue::Number(1.0));
+        assert_eq!(global(&i, "n"), LiteralValue::Number(3.0));
+        assert_eq!(global(&i, "s"), LiteralValue::Number(4.0));
+    }
+
+    #[test]
+    fn compound_assignment_and_increments() {
+        let i = run("var calls = 0;
+            fun next() { calls++; return 10; }
+            var x = 1;
+            x += next(); x -= 2; x *= 3; x /= 9; x %= 2;
+            var s = \"a\"; s += \"b\";
+            var n = 5;
+            var post = n++;
+            var pre = ++n;
+            var down = n--;
+            for (var k = 0; k < 3; k++) { --n; }");
+        assert_eq!(global(&i, "calls"), LiteralValue::Number(1.0));
+        assert_eq!(global(&i, "x"), LiteralValue::Number(1.0));
+        assert_eq!(global(&i, "s"), LiteralValue::StringValue("ab".to_string()));
+        assert_eq!(global(&i, "post"), LiteralValue::Number(5.0));
+        assert_eq!(global(&i, "pre"), LiteralValue::Number(7.0));
+        assert_eq!(global(&i, "down"), LiteralValue::Number(7.0));
+        assert_eq!(global(&i, "n"), LiteralValue::Number(3.0));
+    }
+
+    #[test]
+    fn ternary_and_coalesce_short_circuit() {
+        let i = run("var calls = 0;
+            fun side() { calls++; return 99; }
+            var a = true ? 1 : side();
+            var b = false ? side() : 2;
+            var c = 0 < 1 ? 3 : 1 < 2 ? 4 : 5;
+            var d = nil ?? 6;
+            var e = false ?? side();
+            var f = nil ?? nil ?? 7;");
+        assert_eq!(global(&i, "calls"), LiteralValue::Number(0.0));
+        assert_eq!(global(&i, "a"), LiteralValue::Number(1.0));
+        assert_eq!(global(&i, "b"), LiteralValue::Number(2.0));
+        assert_eq!(global(&i, "c"), LiteralValue::Number(3.0));
+        assert_eq!(global(&i, "d"), LiteralValue::Number(6.0));
+        assert_eq!(global(&i, "e"), LiteralValue::False);
+        assert_eq!(global(&i, "f"), LiteralValue::Number(7.0));
+    }
+
+    #[test]
+    fn power_floor_division_and_bitwise_precedence() {
+        let i = run("var a = 2 ** 3 ** 2;
+            var b = -2 ** 2;
+            var c = 2 ** -1;
+            var d = 7 ~/ 2;
+            var e = -7 ~/ 2;
+            var f = 1 | 2 ^ 3 & 6 << 1;
+            var g = ~5 >> 1;
+            var h = 1 + 2 << 1 == 6;");
+        assert_eq!(global(&i, "a"), LiteralValue::Number(512.0));
+        assert_eq!(global(&i, "b"), LiteralValue::Number(-4.0));
+        assert_eq!(global(&i, "c"), LiteralValue::Number(0.5));
+        assert_eq!(global(&i, "d"), LiteralValue::Number(3.0));
+        assert_eq!(global(&i, "e"), LiteralValue::Number(-4.0));
+        assert_eq!(global(&i, "f"), LiteralValue::Number(3.0));
+        assert_eq!(global(&i, "g"), LiteralValue::Number(-3.0));
+        assert_eq!(global(&i, "h"), LiteralValue::True);
+    }
+
+    #[test]
+    fn one_truthiness_rule_for_if_loops_not_and_bool() {
+        let i = run("var seen = \"\";
+            for (value in [0, \"\", [], nil, false, true]) {
+                if (value) seen = seen + \"t\"; else seen = seen + \"f\";
+                if (!value == bool(value)) seen = seen + \"!\";
+                seen = seen + (value ? \"t\" : \"f\") + (value and \"t\" or \"f\");
+            }
+            var n = 0;
+            var loops = 0;
+            while (n) {
+                loops++;
+                if (loops == 2) n = false;
+            }
+            var fn_is_true = !print;");
+        // only false and nil are false, whichever construct asks
+        assert_eq!(
+            global(&i, "seen"),
+            LiteralValue::StringValue("tttttttttffffffttt".to_string())
+        );
+        assert_eq!(global(&i, "loops"), LiteralValue::Number(2.0));
+        assert_eq!(global(&i, "fn_is_true"), LiteralValue::False);
+    }
+
+    #[test]
+    fn floor_division_and_modulus_reject_a_zero_divisor() {
+        let i = run("var a = 9 ~/ 2 + 5 ~/ 1; // a trailing comment
+            var b = -7 ~/ 2;
+            var zero = [nil, nil];
+            try { 1 ~/ 0; } catch (e) { zero[0] = e.message; }
+            try { 1 % 0; } catch (e) { zero[1] = e.message; }");
+        assert_eq!(global(&i, "a"), LiteralValue::Number(9.0));
+        assert_eq!(global(&i, "b"), LiteralValue::Number(-4.0));
+        let message = LiteralValue::StringValue("Division by zero".to_string());
+        assert_eq!(
+            global(&i, "zero"),
+            LiteralValue::list(vec![message.clone(), message])
+        );
+    }
+
+    #[test]
+    fn match_destructures_lists_and_maps() {
+        let i = run(r#"var out = [];
+            fun describe(v) {
+                match (v) {
+                    0 | 1 => out[0] = "small";
+                    [first, ...rest] if first > 5 => out[1] = rest;
+                    [x, y] => out[2] = x + y;
+                    {"name": n, age} => out[3] = n + age;
+                    _ => out[4] = v;
+                }
+            }
+            out = [nil, nil, nil, nil, nil];
+            describe(1); describe([9, 8, 7]); describe([1, 2]);
+            describe({"name": "a", "age": "b"}); describe("other");"#);
+        let expected = LiteralValue::list(vec![
+            LiteralValue::StringValue("small".to_string()),
+            LiteralValue::list(vec![LiteralValue::Number(8.0), LiteralValue::Number(7.0)]),
+            LiteralValue::Number(3.0),
+            LiteralValue::StringValue("ab".to_string()),
+            LiteralValue::StringValue("other".to_string()),
+        ]);
+        assert_eq!(global(&i, "out"), expected);
+    }
+
+    #[test]
+    fn for_in_walks_collections_ranges_and_iterators() {
+        let i = run(r#"var out = "";
+            for (x in [1, 2]) out += format("{},", x);
+            for (k, v in {b: 2, a: 1}) out += format("{}{}", k, v);
+            for (c in "hé") out += c;
+            for (n in 10..0) out += "never";
+            for (n in range(6, 0, -2)) out += format("{}", n);
+            fun countdown(n) {
+                fun next() { if (n == 0) return nil; n--; return n; }
+                return {next: next};
+            }
+            for (n in countdown(3)) { if (n == 0) break; out += format("{}", n); }"#);
+        assert_eq!(
+            global(&i, "out"),
+            LiteralValue::StringValue("1,2,a1b2hé64221".to_string())
+        );
+    }
+
+    #[test]
+    fn do_while_and_labelled_jumps() {
+        let i = run("var once = 0; do once++; while (false);
+            var pairs = 0;
+            outer: for (var a = 0; a < 4; a++) {
+                for (b in 0..4) {
+                    if (b > a) continue outer;
+                    if (a == 3) break outer;
+                    pairs++;
+                }
+            }");
+        assert_eq!(global(&i, "once"), LiteralValue::Number(1.0));
+        assert_eq!(global(&i, "pairs"), LiteralValue::Number(6.0));
+    }
+
+    #[test]
+    fn misplaced_jumps_are_parse_errors() {
+        for source in [
+            "break;",
+            "while (true) { fun f() { continue; } }",
+            "a: while (true) { break b; }",
+            "return 1;",
+            "fun f() {} return;",
+        ] {
+            let tokens = Scanner::new(source.to_string()).scanTokens();
+            assert!(Parser::new(tokens).parse().is_err(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn return_is_only_allowed_inside_functions() {
+        let parse =
+            |source: &str| Parser::new(Scanner::new(source.to_string()).scanTokens()).parse();
+        assert_eq!(
+            parse("var x = 1;\nwhile (x > 0) { if (x) { return; } }").map(|_| ()),
+            Err("'return' outside of a function at line 2".to_string())
+        );
+        // leaving a nested function body puts the parser back at top level
+        assert_eq!(
+            parse("fun outer() { fun inner() { return 1; } return inner(); }\nreturn 2;")
+                .map(|_| ()),
+            Err("'return' outside of a function at line 2".to_string())
+        );
+        let i = run("fun first_even(xs) {
+                for (x in xs) { if (x % 2 == 0) { return x; } }
+                return nil;
+            }
+            var found = first_even([1, 3, 4, 5]);
+            var none = first_even([1]);");
+        assert_eq!(global(&i, "found"), LiteralValue::Number(4.0));
+        assert_eq!(global(&i, "none"), LiteralValue::Nil);
+    }
+
+    #[test]
+    fn default_rest_and_named_params() {
+        let i = run(r#"fun f(a, b = a * 2, ...rest) { return [a, b, rest]; }
+            var defaults = f(1);
+            var named = f(b: 5, a: 2);
+            var extra = f(1, 2, 3, 4);
+            var message = "";
+            try { f(b: 1); } catch (e) { message = e.message; }"#);
+        let n = LiteralValue::Number;
+        let list = LiteralValue::list;
+        assert_eq!(
+            global(&i, "defaults"),
+            list(vec![n(1.0), n(2.0), list(vec![])])
+        );
+        assert_eq!(
+            global(&i, "named"),
+            list(vec![n(2.0), n(5.0), list(vec![])])
+        );
+        assert_eq!(
+            global(&i, "extra"),
+            list(vec![n(1.0), n(2.0), list(vec![n(3.0), n(4.0)])])
+        );
+        assert_eq!(
+            global(&i, "message"),
+            LiteralValue::StringValue("f is missing argument a".to_string())
+        );
+    }
+
+    #[test]
+    fn constants_and_builtins_cannot_be_reassigned() {
+        let interpreter = Interpreter::new();
+        for source in [
+            "const x = 1; x = 2;",
+            "const x = 1; { x++; }",
+            "var floor = 1;",
+        ] {
+            let tokens = Scanner::new(source.to_string()).scanTokens();
+            let statements = Parser::new(tokens).parse().unwrap();
+            assert!(interpreter.resolve(&statements).is_err(), "{}", source);
+        }
+
+        // assignments the resolver can't see yet still fail at runtime
+        let i = run("var message = \"\";
+            fun f() { x = 5; }
+            const x = 1;
+            try { f(); } catch (e) { message = e.message; }");
+        assert_eq!(
+            global(&i, "message"),
+            LiteralValue::StringValue("Cannot assign to constant x".to_string())
+        );
+        assert_eq!(global(&i, "x"), LiteralValue::Number(1.0));
+    }
+
+    fn resolve(source: &str) -> Result<Vec<String>, String> {
+        let tokens = Scanner::new(source.to_string()).scanTokens();
+        let statements = Parser::new(tokens).parse().unwrap();
+        Interpreter::new().resolve(&statements)
+    }
+
+    #[test]
+    fn resolver_reports_bad_names() {
+        for source in [
+            "print(missing);",
+            "{ var a = 1; { var a = a; } }",
+            "{ var a = 1; var a = 2; print(a); }",
+            "fun f(a, a) {}",
+        ] {
+            assert!(resolve(source).is_err(), "{}", source);
+        }
+        // functions may use names declared after them
+        assert!(resolve("fun f() { return g(); } fun g() { return 1; } f();").is_ok());
+        assert_eq!(
+            resolve("{ var used = 1; var unused = 2; var _skipped = 3; print(used); }"),
+            Ok(vec![
+                "[line 1] Warning: local variable unused is never used".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn assigning_an_undeclared_name_fails_from_any_scope() {
+        let i = run("var message = \"\";
+            fun f() { { undeclared = 1; } }
+            try { f(); } catch (e) { message = e.message; }");
+        assert_eq!(
+            global(&i, "message"),
+            LiteralValue::StringValue("Variable undeclared is not declared".to_string())
+        );
+    }
+
+    #[test]
+    fn math_module() {
+        let i = run("import \"math\";
+            from \"math\" import gcd;
+            var root = math.sqrt(16);
+            var biggest = math.max([4, 9, 2]);
+            var divisor = gcd(12, 18);
+            var message = \"\";
+            try { math.sqrt(\"x\"); } catch (e) { message = e.message; }");
+        assert_eq!(global(&i, "root"), LiteralValue::Number(4.0));
+        assert_eq!(global(&i, "biggest"), LiteralValue::Number(9.0));
+        assert_eq!(global(&i, "divisor"), LiteralValue::Number(6.0));
+        assert_eq!(
+            global(&i, "message"),
+            LiteralValue::StringValue("sqrt expected a number found string".to_string())
+        );
+    }
+
+    #[test]
+    fn string_methods_count_characters() {
+        let i = run("var s = \"héllo wörld\";
+            var size = s.len();
+            var second = s[1];
+            var tail = s[-5..s.len()];
+            var at = s.find(\"wö\");
+            var words = \"-\".join(s.upper().split());
+            var parsed = \" 2.5 \".parse_number();
+            var message = \"\";
+            try { s.substring(3, 99); } catch (e) { message = e.message; }");
+        assert_eq!(global(&i, "size"), LiteralValue::Number(11.0));
+        assert_eq!(global(&i, "second").to_string(), "é");
+        assert_eq!(global(&i, "tail").to_string(), "wörld");
+        assert_eq!(global(&i, "at"), LiteralValue::Number(6.0));
+        assert_eq!(global(&i, "words").to_string(), "HÉLLO-WÖRLD");
+        assert_eq!(global(&i, "parsed"), LiteralValue::Number(2.5));
+        assert_eq!(
+            global(&i, "message").to_string(),
+            "Slice 3..99 out of range for length 11"
+        );
+    }
+
+    #[test]
+    fn file_io_round_trip() {
+        let dir = std::env::temp_dir().join(format!("nox-fs-{}", std::process::id()));
+        let source = format!(
+            "import \"fs\";
+            var dir = \"{}\";
+            fs.mkdir(dir);
+            var f = fs.open(dir + \"/notes.txt\", \"w\");
+            f.write(\"first\\nsecond\\n\");
+            f.close();
+            fs.append_file(dir + \"/notes.txt\", \"third\\n\");
+            var lines = fs.open(dir + \"/notes.txt\").lines();
+            var joined = \"\";
+            for (line in fs.open(dir + \"/notes.txt\")) joined += line + \";\";
+            var names = fs.list_dir(dir);
+            var message = \"\";
+            try {{ fs.read_file(dir + \"/missing.txt\"); }} catch (e) {{ message = e.message; }}",
+            dir.display()
+        );
+        let i = run(&source);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            global(&i, "lines").to_string(),
+            "[\"first\", \"second\", \"third\"]"
+        );
+        assert_eq!(global(&i, "joined").to_string(), "first;second;third;");
+        assert_eq!(global(&i, "names").to_string(), "[\"notes.txt\"]");
+        assert!(
+            global(&i, "message")
+                .to_string()
+                .starts_with("Could not read")
+        );
+    }
+
+    #[test]
+    fn os_module_sees_script_args_and_environment() {
+        // only reads the environment; setting it races other test threads
+        let source = "import \"os\";
+            var args = os.args();
+            var path = os.env(\"PATH\");
+            var missing = os.env(\"NOX_OS_TEST_MISSING\");
+            var seen = [];
+            try {
+                seen = [\"try\"];
+                os.exit(2);
+            } catch (e) {
+                seen = [\"catch\"];
+            } finally {
+                seen = [\"finally\"];
+            }";
+        let tokens = Scanner::new(source.to_string()).scanTokens();
+        let statements = Parser::new(tokens).parse().unwrap();
+        let mut i = Interpreter::new();
+        i.set_args(vec!["a".to_string(), "b c".to_string()]);
+        let Err(RuntimeError::Exit { code: 2 }) = i.interpret_stmt(&statements) else {
+            panic!("os.exit(2) should unwind out of the script");
+        };
+        assert_eq!(global(&i, "args").to_string(), "[\"a\", \"b c\"]");
+        assert_eq!(
+            global(&i, "path"),
+            std::env::var("PATH").map_or(LiteralValue::Nil, LiteralValue::StringValue)
+        );
+        assert_eq!(global(&i, "missing"), LiteralValue::Nil);
+        // exit skips catch and finally
+        assert_eq!(global(&i, "seen").to_string(), "[\"try\"]");
+    }
+
+    #[test]
+    fn date_module_formats_and_parses_utc() {
+        let i = run("import \"date\";
+            var t = date.timestamp(2024, 2, 29, 13, 5, 9);
+            var iso = date.iso(t);
+            var parts = date.parts(t);
+            var text = date.format(t, \"%a %d %b %Y %I:%M %p\");
+            var parsed = date.parse(text, \"%a %d %b %Y %I:%M %p\");
+            var offset = date.parse_iso(\"2024-02-29T15:05:09+02:00\");
+            var span = date.format_duration(date.duration(days: 1, minutes: 3));");
+        assert_eq!(global(&i, "t"), LiteralValue::Number(1709211909.0));
+        assert_eq!(global(&i, "iso").to_string(), "2024-02-29T13:05:09Z");
+        assert_eq!(
+            global(&i, "parts").to_string(),
+            "{\"day\": 29, \"hour\": 13, \"millisecond\": 0, \"minute\": 5, \"month\": 2, \
+             \"second\": 9, \"weekday\": 4, \"yday\": 60, \"year\": 2024}"
+        );
+        assert_eq!(global(&i, "text").to_string(), "Thu 29 Feb 2024 01:05 PM");
+        assert_eq!(global(&i, "parsed"), LiteralValue::Number(1709211900.0));
+        assert_eq!(global(&i, "offset"), LiteralValue::Number(1709211909.0));
+        assert_eq!(global(&i, "span").to_string(), "1d 3m");
+    }
+
+    #[test]
+    fn seeded_random_is_reproducible() {
+        let i = run("import \"random\";
+            fun draw() {
+                random.seed(7);
+                var xs = [1, 2, 3, 4, 5];
+                random.shuffle(xs);
+                return [random.random(), random.randint(1, 6), random.choice(xs), xs];
+            }
+            var first = draw();
+            var second = draw();
+            var in_range = true;
+            for (i in 0..1000) {
+                var n = random.randint(-2, 2);
+                if (n < -2 or n > 2) in_range = false;
+            }");
+        assert_eq!(global(&i, "first"), global(&i, "second"));
+        assert_eq!(global(&i, "in_range"), LiteralValue::True);
+    }
+
+    #[test]
+    fn json_round_trip_and_rejections() {
+        let i = run(
+            r#"var data = json_parse("{\"name\": \"n\\u00f6x\", \"tags\": [1, true, null]}");
+            var who = data.name;
+            var compact = json_stringify(data);
+            var pretty = json_stringify({"a": [1]}, 2);
+            var cyclic = "";
+            var list = [0];
+            list[0] = list;
+            try { json_stringify(list); } catch (e) { cyclic = e.message; }
+            var callable = "";
+            try { json_stringify({"f": print}); } catch (e) { callable = e.message; }
+            var invalid = "";
+            try { json_parse("[1,]"); } catch (e) { invalid = e.message; }"#,
+        );
+        assert_eq!(global(&i, "who").to_string(), "nöx");
+        assert_eq!(
+            global(&i, "compact").to_string(),
+            r#"{"name":"nöx","tags":[1,true,null]}"#
+        );
+        assert_eq!(
+            global(&i, "pretty").to_string(),
+            "{\n  \"a\": [\n    1\n  ]\n}"
+        );
+        assert_eq!(
+            global(&i, "cyclic").to_string(),
+            "Cannot convert a cyclic structure to JSON"
+        );
+        assert_eq!(
+            global(&i, "callable").to_string(),
+            "Cannot convert function to JSON"
+        );
+        assert_eq!(
+            global(&i, "invalid").to_string(),
+            "Invalid JSON at line 1 column 4: unexpected ']'"
+        );
+    }
+
+    #[test]
+    fn self_referencing_lists_print_and_compare() {
+        let i = run("var xs = [1, nil];
+            xs[1] = xs;
+            var ys = [1, nil];
+            ys[1] = ys;
+            var shown = str(xs);
+            var same = xs == ys;
+            xs[1] = nil;
+            ys[1] = nil;");
+        assert_eq!(
+            global(&i, "shown"),
+            LiteralValue::StringValue("[1, [...]]".to_string())
+        );
+        assert_eq!(global(&i, "same"), LiteralValue::True);
+    }
+
+    #[test]
+    fn type_names_and_conversions() {
+        let i = run("fun add(a, b = 1) { return a + b; }
+            var types = [type(1), type(\"s\"), type(true), type(nil), type(add), type([]),
+                type({}), type(0..1)];
+            var converted = [str(12), num(\" 2.5 \"), num(false), bool(0), bool(nil)];
+            var info = [is_callable(print), is_callable(1), arity(floor), arity(add),
+                arity(print), name(add)];
+            var message = \"\";
+            try { num(\"abc\"); } catch (e) { message = e.message; }");
+        assert_eq!(
+            global(&i, "types").to_string(),
+            r#"["number", "string", "bool", "nil", "function", "list", "map", "range"]"#
+        );
+        assert_eq!(
+            global(&i, "converted").to_string(),
+            r#"["12", 2.5, 0, true, false]"#
+        );
+        assert_eq!(
+            global(&i, "info").to_string(),
+            r#"[true, false, 1, {"max": 2, "min": 1}, {"max": nil, "min": 0}, "add"]"#
+        );
+        assert_eq!(
+            global(&i, "message").to_string(),
+            r#"num could not convert "abc" to a number"#
+        );
+    }
+
+    #[test]
+    fn assertions_fail_with_the_call_line() {
+        let i = run("assert(true);
+            assert_eq([1, {\"a\": 2}], [1, {\"a\": 2}]);
+            var plain = \"\";
+            try { assert(0 > 1, \"ordering\"); } catch (e) { plain = e.message; }
+            var equal = \"\";
+            var line = 0;
+            try {
+              assert_eq(\"a\", 1);
+            } catch (e) { equal = e.message; line = e.line; }");
+        assert_eq!(
+            global(&i, "plain").to_string(),
+            "Assertion failed: ordering"
+        );
+        assert_eq!(
+            global(&i, "equal").to_string(),
+            "assert_eq failed: \"a\" != 1"
+        );
+        assert_eq!(global(&i, "line"), LiteralValue::Number(8.0));
+    }
+
+    #[test]
+    fn match_warns_about_missing_and_unreachable_arms() {
+        let tokens = Scanner::new(
+            "match (x) { 1 => x; }
+             match (x) { _ => x; 2 => x; }
+             match (x) { true => x; false => x; }"
+                .to_string(),
+        )
+        .scanTokens();
+        let mut parser = Parser::new(tokens);
+        parser.parse().unwrap();
+        assert_eq!(
+            parser.warnings,
+            vec![
+                "[line 1] Warning: match is not exhaustive, add a '_' arm",
+                "[line 2] Warning: unreachable match arm",
+            ]
+        );
+    }
+
+    #[test]
+    fn finally_runs_on_every_way_out_of_try() {
+        let i = run(r#"var log = "";
+            fun early() {
+                try { return "returned"; } finally { log = log + "r"; }
+            }
+            var returned = early();
+            for (var n = 0; n < 3; n++) {
+                try {
+                    if (n == 0) continue;
+                    if (n == 1) break;
+                } finally { log = log + n; }
+            }
+            var rethrown = nil;
+            try {
+                try { throw "inner"; }
+                catch (e) { throw e + " again"; }
+                finally { log = log + "f"; }
+            } catch (e) { rethrown = e; }
+            var thrown = [0, 0, 0];
+            for (k, value in [42, {"code": 7}, nil]) {
+                try { throw value; } catch (e) { thrown[k] = e; }
+            }"#);
+        assert_eq!(global(&i, "returned").to_string(), "returned");
+        assert_eq!(global(&i, "log").to_string(), "r01f");
+        assert_eq!(global(&i, "rethrown").to_string(), "inner again");
+        assert_eq!(
+            global(&i, "thrown").to_string(),
+            r#"[42, {"code": 7}, nil]"#
+        );
+
+        let tokens = Scanner::new("var x = 1;\nthrow [x, 2];".to_string()).scanTokens();
+        let statements = Parser::new(tokens).parse().unwrap();
+        let Err(error) = Interpreter::new().interpret_stmt(&statements) else {
+            panic!("an uncaught throw should fail the script");
+        };
+        assert_eq!(error.to_string(), "[line 2] Uncaught [1, 2]");
+    }
+
+    #[test]
+    fn return_inside_loop_leaves_function() {
+        let i = run("fun find(n) {
+                for (var i = 0; i < 10; i = i + 1) {
+                    if (i == n) { return i * 10; }
+                }
+                return -1;
+            }
+            var hit = find(4);
+            var miss = find(20);");
+        assert_eq!(global(&i, "hit"), LiteralValue::Number(40.0));
+        assert_eq!(global(&i, "miss"), LiteralValue::Number(-1.0));
+    }
+}
@@ -0,0 +1,453 @@
+use std::{collections::BTreeMap, iter::Peekable, rc::Rc, str::Chars};
+
+use crate::expr::LiteralValue;
+
+// nesting deeper than this is rejected instead of overflowing the stack
+const MAX_DEPTH: usize = 512;
+
+// arrays become lists, objects become maps, null becomes nil
+pub fn parse(text: &str) -> Result<LiteralValue, String> {
+    let mut parser = Parser {
+        chars: text.chars().peekable(),
+        line: 1,
+        column: 1,
+    };
+    parser.whitespace();
+    let value = parser.value(0)?;
+    parser.whitespace();
+    if parser.chars.peek().is_some() {
+        return Err(parser.error("unexpected text after the value"));
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    column: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> String {
+        format!(
+            "Invalid JSON at line {} column {}: {}",
+            self.line, self.column, message
+        )
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn whitespace(&mut self) {
+        while self
+            .chars
+            .peek()
+            .is_some_and(|c| matches!(c, ' ' | '\t' | '\n' | '\r'))
+        {
+            self.next();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(self.error(&format!("expected '{}' found '{}'", expected, c))),
+            None => Err(self.error(&format!("expected '{}' found end of input", expected))),
+        }
+    }
+
+    fn keyword(&mut self, word: &str, value: LiteralValue) -> Result<LiteralValue, String> {
+        for expected in word.chars() {
+            if self.next() != Some(expected) {
+                return Err(self.error(&format!("expected '{}'", word)));
+            }
+        }
+        Ok(value)
+    }
+
+    fn value(&mut self, depth: usize) -> Result<LiteralValue, String> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("nesting is too deep"));
+        }
+        match self.chars.peek().copied() {
+            Some('{') => self.object(depth),
+            Some('[') => self.array(depth),
+            Some('"') => Ok(LiteralValue::StringValue(self.string()?)),
+            Some('t') => self.keyword("true", LiteralValue::True),
+            Some('f') => self.keyword("false", LiteralValue::False),
+            Some('n') => self.keyword("null", LiteralValue::Nil),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+            Some(c) => Err(self.error(&format!("unexpected '{}'", c))),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn array(&mut self, depth: usize) -> Result<LiteralValue, String> {
+        self.expect('[')?;
+        let mut items = vec![];
+        self.whitespace();
+        if self.chars.next_if_eq(&']').is_some() {
+            self.column += 1;
+            return Ok(LiteralValue::list(items));
+        }
+        loop {
+            self.whitespace();
+            items.push(self.value(depth + 1)?);
+            self.whitespace();
+            match self.next() {
+                Some(',') => continue,
+                Some(']') => return Ok(LiteralValue::list(items)),
+                _ => return Err(self.error("expected ',' or ']' in array")),
+            }
+        }
+    }
+
+    fn object(&mut self, depth: usize) -> Result<LiteralValue, String> {
+        self.expect('{')?;
+        let mut entries = BTreeMap::new();
+        self.whitespace();
+        if self.chars.next_if_eq(&'}').is_some() {
+            self.column += 1;
+            return Ok(LiteralValue::map(entries));
+        }
+        loop {
+            self.whitespace();
+            if self.chars.peek() != Some(&'"') {
+                return Err(self.error("expected a string key"));
+            }
+            let key = self.string()?;
+            self.whitespace();
+            self.expect(':')?;
+            self.whitespace();
+            entries.insert(key, self.value(depth + 1)?);
+            self.whitespace();
+            match self.next() {
+                Some(',') => continue,
+                Some('}') => return Ok(LiteralValue::map(entries)),
+                _ => return Err(self.error("expected ',' or '}' in object")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut out = String::new();
+        loop {
+            match self.next() {
+                Some('"') => return Ok(out),
+                Some('\\') => {
+                    let c = match self.next() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => self.unicode_escape()?,
+                        _ => return Err(self.error("invalid escape in string")),
+                    };
+                    out.push(c);
+                }
+                Some(c) if (c as u32) < 0x20 => {
+                    return Err(self.error("control character in string"));
+                }
+                Some(c) => out.push(c),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self
+                .next()
+                .and_then(|c| c.to_digit(16))
+                .ok_or_else(|| self.error("invalid \\u escape"))?;
+            code = code * 16 + digit;
+        }
+        Ok(code)
+    }
+
+    // `\uXXXX`, joining a surrogate pair into one character
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.hex4()?;
+        let code = if (0xD800..0xDC00).contains(&high) {
+            if self.next() != Some('\\') || self.next() != Some('u') {
+                return Err(self.error("unpaired surrogate in \\u escape"));
+            }
+            let low = self.hex4()?;
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err(self.error("unpaired surrogate in \\u escape"));
+            }
+            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+        } else {
+            high
+        };
+        char::from_u32(code).ok_or_else(|| self.error("unpaired surrogate in \\u escape"))
+    }
+
+    fn number(&mut self) -> Result<LiteralValue, String> {
+        let mut text = String::new();
+        while let Some(c) = self
+            .chars
+            .next_if(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
+        {
+            self.column += 1;
+            text.push(c);
+        }
+        // Rust accepts a few forms JSON doesn't, like "01" or "1."
+        let digits = text.trim_start_matches('-');
+        let leading_zero =
+            digits.starts_with('0') && digits[1..].starts_with(|c: char| c.is_ascii_digit());
+        let bare_point = digits.starts_with('.')
+            || text.ends_with('.')
+            || text.contains(".e")
+            || text.contains(".E");
+        match text.parse::<f64>() {
+            Ok(x) if !leading_zero && !bare_point && x.is_finite() => Ok(LiteralValue::Number(x)),
+            _ => Err(self.error(&format!("invalid number {}", text))),
+        }
+    }
+}
+
+// `indent` is the number of spaces per level; None gives compact output
+pub fn stringify(value: &LiteralValue, indent: Option<usize>) -> Result<String, String> {
+    let mut out = String::new();
+    let mut writer = Writer {
+        out: &mut out,
+        indent,
+        open: vec![],
+    };
+    writer.value(value, 0)?;
+    Ok(out)
+}
+
+struct Writer<'a> {
+    out: &'a mut String,
+    indent: Option<usize>,
+    // the lists and maps currently being written, to catch cycles
+    open: Vec<*const ()>,
+}
+
+impl Writer<'_> {
+    fn newline(&mut self, level: usize) {
+        if let Some(width) = self.indent {
+            self.out.push('\n');
+            self.out.push_str(&" ".repeat(width * level));
+        }
+    }
+
+    fn enter(&mut self, ptr: *const ()) -> Result<(), String> {
+        if self.open.contains(&ptr) {
+            return Err("Cannot convert a cyclic structure to JSON".to_string());
+        }
+        self.open.push(ptr);
+        Ok(())
+    }
+
+    fn value(&mut self, value: &LiteralValue, level: usize) -> Result<(), String> {
+        if level > MAX_DEPTH {
+            return Err("Cannot convert to JSON: nesting is too deep".to_string());
+        }
+        match value {
+            LiteralValue::Nil => self.out.push_str("null"),
+            LiteralValue::True => self.out.push_str("true"),
+            LiteralValue::False => self.out.push_str("false"),
+            LiteralValue::Number(x) if x.is_finite() => self.out.push_str(&x.to_string()),
+            LiteralValue::Number(x) => return Err(format!("Cannot convert {} to JSON", x)),
+            LiteralValue::StringValue(s) => self.string(s),
+            LiteralValue::List(items) => {
+                self.enter(Rc::as_ptr(items) as *const ())?;
+                let items = items.borrow();
+                self.out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push(',');
+                    }
+                    self.newline(level + 1);
+                    self.value(item, level + 1)?;
+                }
+                if !items.is_empty() {
+                    self.newline(level);
+                }
+                self.out.push(']');
+                self.open.pop();
+            }
+            LiteralValue::Map(entries) => {
+                self.enter(Rc::as_ptr(entries) as *const ())?;
+                let entries = entries.borrow();
+                self.out.push('{');
+                for (i, (key, item)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push(',');
+                    }
+                    self.newline(level + 1);
+                    self.string(key);
+                    self.out
+                        .push_str(if self.indent.is_some() { ": " } else { ":" });
+                    self.value(item, level + 1)?;
+                }
+                if !entries.is_empty() {
+                    self.newline(level);
+                }
+                self.out.push('}');
+                self.open.pop();
+            }
+            other => return Err(format!("Cannot convert {} to JSON", other.to_type())),
+        }
+        Ok(())
+    }
+
+    fn string(&mut self, s: &str) {
+        self.out.push('"');
+        for c in s.chars() {
+            match c {
+                '"' => self.out.push_str("\\\""),
+                '\\' => self.out.push_str("\\\\"),
+                '\n' => self.out.push_str("\\n"),
+                '\r' => self.out.push_str("\\r"),
+                '\t' => self.out.push_str("\\t"),
+                '\u{8}' => self.out.push_str("\\b"),
+                '\u{c}' => self.out.push_str("\\f"),
+                c if (c as u32) < 0x20 => self.out.push_str(&format!("\\u{:04x}", c as u32)),
+                c => self.out.push(c),
+            }
+        }
+        self.out.push('"');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> LiteralValue {
+        LiteralValue::StringValue(s.to_string())
+    }
+
+    // parse then print compactly, so whole structures compare as strings
+    fn round_trip(json: &str) -> Result<String, String> {
+        stringify(&parse(json)?, None)
+    }
+
+    #[test]
+    fn escapes_and_surrogate_pairs_decode() {
+        assert_eq!(
+            parse(r#""a\"b\\c\/d\b\f\n\r\t""#),
+            Ok(text("a\"b\\c/d\u{8}\u{c}\n\r\t"))
+        );
+        assert_eq!(parse(r#""\u00e9\u4E2D""#), Ok(text("\u{e9}\u{4e2d}")));
+        assert_eq!(parse(r#""\ud83d\ude00!""#), Ok(text("\u{1f600}!")));
+        for bad in [
+            r#""\ud83d""#,
+            r#""\ud83dx""#,
+            r#""\ud83d\u0041""#,
+            r#""\ude00""#,
+            r#""\u12g4""#,
+            r#""\x""#,
+            "\"a\nb\"",
+            r#""open"#,
+        ] {
+            assert!(parse(bad).is_err(), "{}", bad);
+        }
+        assert_eq!(
+            parse(r#""\ud83d""#),
+            Err("Invalid JSON at line 1 column 9: unpaired surrogate in \\u escape".to_string())
+        );
+    }
+
+    #[test]
+    fn numbers_follow_the_json_grammar() {
+        assert_eq!(parse("-0.5e2"), Ok(LiteralValue::Number(-50.0)));
+        assert_eq!(parse("0"), Ok(LiteralValue::Number(0.0)));
+        for bad in ["01", "-01", "1.", ".5", "1.e3", "-", "1e", "+1", "1e999"] {
+            assert!(parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn trailing_text_and_bad_structure_are_reported_with_positions() {
+        assert_eq!(
+            parse("[1, 2] x"),
+            Err("Invalid JSON at line 1 column 8: unexpected text after the value".to_string())
+        );
+        assert_eq!(
+            parse("{\n  \"a\" 1}"),
+            Err("Invalid JSON at line 2 column 8: expected ':' found '1'".to_string())
+        );
+        for bad in ["", "[1,]", "{\"a\":1,}", "{1:2}", "[1 2]", "nul", "truex"] {
+            assert!(parse(bad).is_err(), "{:?}", bad);
+        }
+        assert_eq!(
+            round_trip(" { \"b\" : [ true , null ] , \"a\" : { } } "),
+            Ok(r#"{"a":{},"b":[true,null]}"#.to_string())
+        );
+    }
+
+    #[test]
+    fn nesting_is_limited_both_ways() {
+        let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(parse(&nested(MAX_DEPTH + 1)).is_ok());
+        assert!(
+            parse(&nested(MAX_DEPTH + 2))
+                .unwrap_err()
+                .ends_with("nesting is too deep")
+        );
+        // far past the limit still fails cleanly instead of overflowing
+        assert!(parse(&nested(100_000)).is_err());
+
+        let mut deep = LiteralValue::list(vec![]);
+        for _ in 0..MAX_DEPTH + 1 {
+            deep = LiteralValue::list(vec![deep]);
+        }
+        assert_eq!(
+            stringify(&deep, None),
+            Err("Cannot convert to JSON: nesting is too deep".to_string())
+        );
+    }
+
+    #[test]
+    fn stringify_indents_escapes_and_rejects_cycles() {
+        let value = parse(r#"{"b": [1, "x\u0001"], "a": {}, "c": []}"#).unwrap();
+        assert_eq!(
+            stringify(&value, Some(2)),
+            Ok(
+                "{\n  \"a\": {},\n  \"b\": [\n    1,\n    \"x\\u0001\"\n  ],\n  \"c\": []\n}"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            stringify(&text("\"\\\n\t"), None),
+            Ok(r#""\"\\\n\t""#.to_string())
+        );
+        assert!(stringify(&LiteralValue::Number(f64::NAN), None).is_err());
+
+        let list = LiteralValue::list(vec![]);
+        if let LiteralValue::List(items) = &list {
+            items.borrow_mut().push(list.clone());
+        }
+        assert_eq!(
+            stringify(&list, None),
+            Err("Cannot convert a cyclic structure to JSON".to_string())
+        );
+        // the same list twice side by side is not a cycle
+        let shared = LiteralValue::list(vec![LiteralValue::Nil]);
+        let pair = LiteralValue::list(vec![shared.clone(), shared]);
+        assert_eq!(stringify(&pair, None), Ok("[[null],[null]]".to_string()));
+        // break the cycle so the test doesn't leak it
+        if let LiteralValue::List(items) = &list {
+            items.borrow_mut().clear();
+        }
+    }
+}
@@ -1,46 +1,101 @@
+#![allow(non_camel_case_types, non_snake_case, clippy::upper_case_acronyms)]
+
 use std::{
-    env::{self, args},
+    env,
     fs::read_to_string,
-    io::{BufRead, BufReader, Write, stdin, stdout},
+    io::{BufRead, Write, stdin, stdout},
+    path::Path,
     process::exit,
 };
 
+use error::RuntimeError;
 use interpreter::Interpreter;
 use parser::Parser;
 use scanner::Scanner;
 use token::Token;
 
+mod builtins;
+mod date;
 mod environment;
+mod error;
 mod expr;
+mod files;
 mod interpreter;
+mod json;
+mod math;
+mod module;
+mod os;
 mod parser;
+mod random;
+mod resolver;
 mod scanner;
 mod stmt;
+mod strings;
+mod test_runner;
 mod token;
 mod tokentype;
 
-#[allow(warnings)]
 fn main() {
     let args: Vec<String> = env::args().collect();
-    println!("{:?}", args);
-    let interpreter = Interpreter::new();
-    if args.len() > 2 {
-        println!("Using : jlox [script]");
-        exit(64);
-    } else if args.len() == 2 {
-        let path = args.get(1).unwrap();
-        execute_file(&path);
+    if args.get(1).is_some_and(|a| a == "test") {
+        let dir = args.get(2).map_or(".", |d| d.as_str());
+        match test_runner::run_tests(Path::new(dir), &mut stdout()) {
+            Ok(true) => (),
+            Ok(false) => exit(1),
+            Err(e) => {
+                eprintln!("{}", e);
+                exit(66);
+            }
+        }
+    } else if let Some(path) = args.get(1) {
+        execute_file(path, args[2..].to_vec());
     } else {
-        run_prompt();
+        if let Err(e) = run_prompt() {
+            eprintln!("{}", e);
+        }
+        finish(0);
     }
 }
-fn execute_file(path: &String) {
+fn execute_file(path: &str, args: Vec<String>) {
     let mut interpreter = Interpreter::new();
-    let data = read_to_string(path).unwrap();
-    match run(&mut interpreter, data) {
-        Ok(_) => (),
-        Err(e) => println!("{}", e),
+    interpreter.set_script(Path::new(path));
+    interpreter.set_args(args);
+    let data = match read_to_string(path) {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("Could not read {}: {}", path, e);
+            exit(66);
+        }
+    };
+    let result = run(&mut interpreter, data);
+    drop(interpreter);
+    let code = match result {
+        Ok(()) => 0,
+        Err(Failure::Static(e)) => {
+            eprintln!("{}", e);
+            65
+        }
+        Err(Failure::Runtime(e)) => {
+            eprintln!("{}", e);
+            70
+        }
+        Err(Failure::Exit(code)) => code,
+    };
+    finish(code);
+}
+
+// flushes files a script left open, then exits with `code`, or 74 if a
+// flush failed and nothing else did
+fn finish(code: i32) -> ! {
+    let errors = files::flush_open();
+    for error in &errors {
+        eprintln!("{}", error);
     }
+    exit(if code == 0 && !errors.is_empty() {
+        74
+    } else {
+        code
+    })
 }
 
 fn run_prompt() -> Result<(), String> {
@@ -53,9 +108,8 @@ fn run_prompt() -> Result<(), String> {
             Err(_) => return Err("could not not flush stdout".to_string()),
         }
         let mut buffer = String::new();
-        let stdin = stdin();
-        let mut handle = stdin.lock();
-        match handle.read_line(&mut buffer) {
+        // the lock is released before the line runs, so `input()` can take it
+        match stdin().lock().read_line(&mut buffer) {
             Ok(n) => {
                 if n <= 1 {
                     return Ok(());
@@ -63,22 +117,42 @@ fn run_prompt() -> Result<(), String> {
             }
             Err(_) => return Err("coudn't read line".to_string()),
         }
-        print!("");
         match run(&mut interpreter, buffer) {
-            Ok(_) => (),
-            Err(m) => println!("{}", m),
+            Ok(()) => (),
+            Err(Failure::Static(e) | Failure::Runtime(e)) => eprintln!("{}", e),
+            Err(Failure::Exit(code)) => {
+                drop(interpreter);
+                finish(code);
+            }
         }
     }
 }
 
-fn run(interpreter: &mut Interpreter, bytes: String) -> Result<(), String> {
+// why a script or REPL line stopped early
+enum Failure {
+    // a parse or resolve error, found before anything ran
+    Static(String),
+    Runtime(String),
+    Exit(i32),
+}
+
+fn run(interpreter: &mut Interpreter, bytes: String) -> Result<(), Failure> {
     let scanner: Scanner = Scanner::new(bytes);
 
     let tokens: Vec<Token> = scanner.scanTokens();
 
     let mut parser = Parser::new(tokens);
-    let statements = parser.parse()?;
-    interpreter.interpret_stmt(&statements)?;
+    let statements = parser.parse().map_err(Failure::Static)?;
+    let warnings = interpreter.resolve(&statements).map_err(Failure::Static)?;
+    for warning in parser.warnings.iter().chain(&warnings) {
+        eprintln!("{}", warning);
+    }
+    interpreter
+        .interpret_stmt(&statements)
+        .map_err(|e| match e {
+            RuntimeError::Exit { code } => Failure::Exit(code),
+            other => Failure::Runtime(other.to_string()),
+        })?;
     //println!("{}",res.to_string());
     //println!("{:#?}",tokens);
     Ok(())
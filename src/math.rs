@@ -0,0 +1,277 @@
+use std::{f64::consts, rc::Rc};
+
+use crate::{
+    builtins::{native, number},
+    environment::Environment,
+    error::RuntimeError,
+    expr::{Arity, LiteralValue},
+};
+
+type Unary = fn(f64) -> f64;
+
+// a callable wrapping a plain `f64 -> f64` function
+fn unary(name: &'static str, f: Unary) -> LiteralValue {
+    LiteralValue::Callable {
+        name: name.to_string(),
+        arity: Arity::exact(1),
+        params: Rc::from([]),
+        keywords: Rc::from([]),
+        fun: Rc::new(move |_env, args| Ok(LiteralValue::Number(f(number(name, &args[0])?)))),
+    }
+}
+
+pub fn module() -> Environment {
+    let mut env = Environment::new();
+    for (name, value) in [
+        ("pi", consts::PI),
+        ("e", consts::E),
+        ("inf", f64::INFINITY),
+        ("nan", f64::NAN),
+    ] {
+        env.define_const(name, LiteralValue::Number(value));
+    }
+    let unaries: [(&'static str, Unary); 22] = [
+        ("sqrt", f64::sqrt),
+        ("abs", f64::abs),
+        ("floor", f64::floor),
+        ("ceil", f64::ceil),
+        ("round", f64::round),
+        ("trunc", f64::trunc),
+        ("sin", f64::sin),
+        ("cos", f64::cos),
+        ("tan", f64::tan),
+        ("asin", f64::asin),
+        ("acos", f64::acos),
+        ("atan", f64::atan),
+        ("sinh", f64::sinh),
+        ("cosh", f64::cosh),
+        ("tanh", f64::tanh),
+        ("asinh", f64::asinh),
+        ("acosh", f64::acosh),
+        ("atanh", f64::atanh),
+        ("exp", f64::exp),
+        ("log2", f64::log2),
+        ("log10", f64::log10),
+        ("sign", sign),
+    ];
+    for (name, f) in unaries {
+        env.define_const(name, unary(name, f));
+    }
+    env.define_const(
+        "pow",
+        native("pow", Arity::exact(2), |_, args| {
+            let x = number("pow", &args[0])?;
+            Ok(LiteralValue::Number(x.powf(number("pow", &args[1])?)))
+        }),
+    );
+    env.define_const(
+        "atan2",
+        native("atan2", Arity::exact(2), |_, args| {
+            let y = number("atan2", &args[0])?;
+            Ok(LiteralValue::Number(y.atan2(number("atan2", &args[1])?)))
+        }),
+    );
+    // log(x) is the natural log, log(x, base) any other
+    env.define_const(
+        "log",
+        native("log", Arity::between(1, 2), |_, args| {
+            let x = number("log", &args[0])?;
+            Ok(LiteralValue::Number(match args.get(1) {
+                Some(base) => x.log(number("log", base)?),
+                None => x.ln(),
+            }))
+        }),
+    );
+    env.define_const(
+        "is_nan",
+        native("is_nan", Arity::exact(1), |_, args| {
+            Ok(LiteralValue::from_bool(
+                number("is_nan", &args[0])?.is_nan(),
+            ))
+        }),
+    );
+    env.define_const(
+        "is_finite",
+        native("is_finite", Arity::exact(1), |_, args| {
+            Ok(LiteralValue::from_bool(
+                number("is_finite", &args[0])?.is_finite(),
+            ))
+        }),
+    );
+    env.define_const(
+        "min",
+        native("min", Arity::at_least(1), |_, args| {
+            extreme("min", args, f64::min)
+        }),
+    );
+    env.define_const(
+        "max",
+        native("max", Arity::at_least(1), |_, args| {
+            extreme("max", args, f64::max)
+        }),
+    );
+    env.define_const(
+        "clamp",
+        native("clamp", Arity::exact(3), |_, args| {
+            let x = number("clamp", &args[0])?;
+            let low = number("clamp", &args[1])?;
+            let high = number("clamp", &args[2])?;
+            // f64::clamp panics on NaN bounds as well as reversed ones
+            if low.is_nan() || high.is_nan() {
+                return Err(format!("clamp bounds {} and {} must be numbers", low, high).into());
+            }
+            if low > high {
+                return Err(format!("clamp bounds {} and {} are out of order", low, high).into());
+            }
+            Ok(LiteralValue::Number(x.clamp(low, high)))
+        }),
+    );
+    env.define_const(
+        "gcd",
+        native("gcd", Arity::exact(2), |_, args| {
+            Ok(LiteralValue::Number(gcd(
+                integer("gcd", &args[0])?,
+                integer("gcd", &args[1])?,
+            )))
+        }),
+    );
+    env.define_const(
+        "lcm",
+        native("lcm", Arity::exact(2), |_, args| {
+            let a = integer("lcm", &args[0])?;
+            let b = integer("lcm", &args[1])?;
+            if a == 0.0 || b == 0.0 {
+                return Ok(LiteralValue::Number(0.0));
+            }
+            Ok(LiteralValue::Number((a / gcd(a, b) * b).abs()))
+        }),
+    );
+    env
+}
+
+// unlike f64::signum, zero keeps its own sign
+fn sign(x: f64) -> f64 {
+    if x == 0.0 || x.is_nan() {
+        x
+    } else {
+        x.signum()
+    }
+}
+
+// min/max take either several numbers or a single list of them
+fn extreme(
+    name: &str,
+    args: &[LiteralValue],
+    pick: fn(f64, f64) -> f64,
+) -> Result<LiteralValue, RuntimeError> {
+    let items = match args {
+        [LiteralValue::List(items)] => items.borrow().clone(),
+        _ => args.to_vec(),
+    };
+    let mut result: Option<f64> = None;
+    for item in &items {
+        let x = number(name, item)?;
+        result = Some(result.map_or(x, |r| pick(r, x)));
+    }
+    result
+        .map(LiteralValue::Number)
+        .ok_or_else(|| format!("{} of an empty list", name).into())
+}
+
+fn integer(name: &str, value: &LiteralValue) -> Result<f64, String> {
+    let x = number(name, value)?;
+    if x.fract() != 0.0 || !x.is_finite() {
+        return Err(format!("{} expected an integer found {}", name, x));
+    }
+    Ok(x)
+}
+
+fn gcd(mut a: f64, mut b: f64) -> f64 {
+    while b != 0.0 {
+        (a, b) = (b, a % b);
+    }
+    a.abs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbers(xs: &[f64]) -> Vec<LiteralValue> {
+        xs.iter().map(|x| LiteralValue::Number(*x)).collect()
+    }
+
+    #[test]
+    fn sign_keeps_zero_and_nan() {
+        assert_eq!(sign(-3.5), -1.0);
+        assert_eq!(sign(2.0), 1.0);
+        assert!(sign(-0.0).is_sign_negative() && sign(-0.0) == 0.0);
+        assert!(sign(0.0).is_sign_positive());
+        assert!(sign(f64::NAN).is_nan());
+    }
+
+    #[test]
+    fn extreme_takes_varargs_or_one_list() {
+        let max = |args: &[LiteralValue]| extreme("max", args, f64::max);
+        assert_eq!(
+            max(&numbers(&[1.0, 5.0, -2.0])).ok(),
+            Some(LiteralValue::Number(5.0))
+        );
+        assert_eq!(
+            max(&[LiteralValue::list(numbers(&[3.0, 4.0]))]).ok(),
+            Some(LiteralValue::Number(4.0))
+        );
+        assert_eq!(
+            max(&[LiteralValue::list(vec![])]).map_err(|e| e.to_string()),
+            Err("[line 0] max of an empty list".to_string())
+        );
+        let mixed = [LiteralValue::Number(1.0), LiteralValue::Nil];
+        assert!(max(&mixed).is_err());
+    }
+
+    #[test]
+    fn gcd_and_lcm_work_on_integers() {
+        assert_eq!(gcd(12.0, 18.0), 6.0);
+        assert_eq!(gcd(-12.0, 18.0), 6.0);
+        assert_eq!(gcd(0.0, 7.0), 7.0);
+        assert_eq!(gcd(0.0, 0.0), 0.0);
+        for bad in [1.5, f64::INFINITY, f64::NAN] {
+            assert!(
+                integer("gcd", &LiteralValue::Number(bad)).is_err(),
+                "{}",
+                bad
+            );
+        }
+        let module = module();
+        let lcm = module.get("lcm").unwrap();
+        let call = |a, b| {
+            lcm.call(Rc::new(Environment::new().into()), numbers(&[a, b]))
+                .ok()
+        };
+        assert_eq!(call(4.0, -6.0), Some(LiteralValue::Number(12.0)));
+        assert_eq!(call(0.0, 5.0), Some(LiteralValue::Number(0.0)));
+    }
+
+    #[test]
+    fn clamp_rejects_nan_and_reversed_bounds() {
+        let module = module();
+        let clamp = module.get("clamp").unwrap();
+        let call = |x, low, high| {
+            clamp
+                .call(Rc::new(Environment::new().into()), numbers(&[x, low, high]))
+                .map_err(|e| e.to_string())
+        };
+        assert_eq!(call(5.0, 1.0, 3.0), Ok(LiteralValue::Number(3.0)));
+        assert_eq!(
+            call(1.0, f64::NAN, 2.0),
+            Err("[line 0] clamp bounds NaN and 2 must be numbers".to_string())
+        );
+        assert!(call(1.0, 0.0, f64::NAN).is_err());
+        assert_eq!(
+            call(1.0, 2.0, 0.0),
+            Err("[line 0] clamp bounds 2 and 0 are out of order".to_string())
+        );
+        // a NaN value passes through, since only the bounds can panic
+        assert!(matches!(call(f64::NAN, 0.0, 1.0), Ok(LiteralValue::Number(x)) if x.is_nan()));
+    }
+}
@@ -0,0 +1,142 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    env,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::{builtins, environment::Environment};
+
+// Modules already executed, plus the chain of files currently being imported
+// so `a -> b -> a` is reported instead of recursing forever.
+pub struct ModuleCache {
+    loaded: HashMap<PathBuf, Rc<RefCell<Environment>>>,
+    loading: Vec<PathBuf>,
+    natives: HashMap<String, Rc<RefCell<Environment>>>,
+    // script arguments, handed to the `os` module
+    pub args: Vec<String>,
+    search_path: Vec<PathBuf>,
+}
+
+impl ModuleCache {
+    pub fn new() -> Self {
+        let search_path = match env::var_os("NOX_PATH") {
+            Some(paths) => env::split_paths(&paths).collect(),
+            None => vec![],
+        };
+        Self {
+            loaded: HashMap::new(),
+            loading: vec![],
+            natives: HashMap::new(),
+            args: vec![],
+            search_path,
+        }
+    }
+
+    // `spec` is looked up next to the importing file first (the working
+    // directory for the REPL), then in each NOX_PATH directory
+    pub fn resolve(&self, importer: Option<&Path>, spec: &str) -> Result<PathBuf, String> {
+        let base = importer
+            .and_then(|p| p.parent())
+            .map(|p| p.to_path_buf())
+            .unwrap_or_default();
+        let candidates = std::iter::once(base).chain(self.search_path.iter().cloned());
+        for dir in candidates {
+            let path = dir.join(spec);
+            if path.is_file() {
+                return path
+                    .canonicalize()
+                    .map_err(|e| format!("Could not import {}: {}", spec, e));
+            }
+        }
+        Err(format!("Module {} not found", spec))
+    }
+
+    // standard library modules such as `math`, built on first import
+    pub fn native(&mut self, name: &str) -> Option<Rc<RefCell<Environment>>> {
+        if !self.natives.contains_key(name) {
+            let module = builtins::native_module(name, &self.args)?;
+            self.natives
+                .insert(name.to_string(), Rc::new(RefCell::new(module)));
+        }
+        self.natives.get(name).cloned()
+    }
+
+    pub fn get(&self, path: &Path) -> Option<Rc<RefCell<Environment>>> {
+        self.loaded.get(path).cloned()
+    }
+
+    pub fn begin(&mut self, path: &Path) -> Result<(), String> {
+        if let Some(start) = self.loading.iter().position(|p| p == path) {
+            let cycle: Vec<String> = self.loading[start..]
+                .iter()
+                .chain(std::iter::once(&path.to_path_buf()))
+                .map(|p| p.display().to_string())
+                .collect();
+            return Err(format!("Import cycle detected: {}", cycle.join(" -> ")));
+        }
+        self.loading.push(path.to_path_buf());
+        Ok(())
+    }
+
+    pub fn finish(&mut self, path: &Path, module: Option<Rc<RefCell<Environment>>>) {
+        self.loading.retain(|p| p != path);
+        if let Some(module) = module {
+            self.loaded.insert(path.to_path_buf(), module);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn resolve_prefers_the_importer_then_searches_nox_path() {
+        let root = env::temp_dir().join(format!("nox-resolve-{}", std::process::id()));
+        let (local, shared) = (root.join("app"), root.join("shared"));
+        fs::create_dir_all(&local).unwrap();
+        fs::create_dir_all(&shared).unwrap();
+        for path in [local.join("util.nox"), shared.join("util.nox")] {
+            fs::write(path, "").unwrap();
+        }
+        fs::write(shared.join("extra.nox"), "").unwrap();
+
+        let cache = ModuleCache {
+            search_path: vec![shared.clone()],
+            ..ModuleCache::new()
+        };
+        let importer = local.join("main.nox");
+        let found = |spec| cache.resolve(Some(&importer), spec);
+        let local_util = found("util.nox");
+        let shared_extra = found("extra.nox");
+        let missing = found("missing.nox");
+        let expected = (
+            local.join("util.nox").canonicalize().unwrap(),
+            shared.join("extra.nox").canonicalize().unwrap(),
+        );
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(local_util, Ok(expected.0));
+        assert_eq!(shared_extra, Ok(expected.1));
+        assert_eq!(missing, Err("Module missing.nox not found".to_string()));
+    }
+
+    #[test]
+    fn begin_reports_the_whole_cycle() {
+        let mut cache = ModuleCache::new();
+        let (a, b) = (Path::new("/a.nox"), Path::new("/b.nox"));
+        assert!(cache.begin(a).is_ok());
+        assert!(cache.begin(b).is_ok());
+        assert_eq!(
+            cache.begin(a),
+            Err("Import cycle detected: /a.nox -> /b.nox -> /a.nox".to_string())
+        );
+        cache.finish(b, None);
+        cache.finish(a, None);
+        assert!(cache.begin(a).is_ok());
+    }
+}
@@ -0,0 +1,248 @@
+use std::{cell::RefCell, collections::BTreeMap, env, process::Command, rc::Rc};
+
+use crate::{
+    builtins::{Builtin, native, number, string},
+    environment::Environment,
+    error::RuntimeError,
+    expr::{Arity, LiteralValue},
+};
+
+type EnvRef = Rc<RefCell<Environment>>;
+
+// `script_args` are whatever followed the script path on the command line
+pub fn module(script_args: &[String]) -> Environment {
+    let mut env = Environment::new();
+    let functions: [(&str, Arity, Builtin); 5] = [
+        ("env", Arity::between(0, 1), get_env),
+        ("set_env", Arity::exact(2), set_env),
+        ("exit", Arity::between(0, 1), exit),
+        ("cwd", Arity::exact(0), cwd),
+        ("run", Arity::between(1, 2), run),
+    ];
+    for (name, arity, fun) in functions {
+        env.define_const(name, native(name, arity, fun));
+    }
+    let args: Rc<[String]> = script_args.into();
+    env.define_const(
+        "args",
+        LiteralValue::Callable {
+            name: "args".to_string(),
+            arity: Arity::exact(0),
+            params: Rc::from([]),
+            keywords: Rc::from([]),
+            fun: Rc::new(move |_env, _args| {
+                let args = args.iter().cloned().map(LiteralValue::StringValue);
+                Ok(LiteralValue::list(args.collect()))
+            }),
+        },
+    );
+    env
+}
+
+// env(name) is nil when the variable isn't set; env() maps every variable
+fn get_env(_env: EnvRef, args: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let Some(name) = args.first() else {
+        let vars: BTreeMap<String, LiteralValue> = env::vars_os()
+            .map(|(k, v)| {
+                (
+                    k.to_string_lossy().into_owned(),
+                    LiteralValue::StringValue(v.to_string_lossy().into_owned()),
+                )
+            })
+            .collect();
+        return Ok(LiteralValue::map(vars));
+    };
+    let name = string("env", name)?;
+    Ok(match env::var_os(name) {
+        Some(value) => LiteralValue::StringValue(value.to_string_lossy().into_owned()),
+        None => LiteralValue::Nil,
+    })
+}
+
+fn set_env(_env: EnvRef, args: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let name = string("set_env", &args[0])?;
+    let value = string("set_env", &args[1])?;
+    if name.is_empty() || name.contains(['=', '\0']) {
+        return Err(format!("Invalid environment variable name {:?}", name).into());
+    }
+    if value.contains('\0') {
+        return Err(format!("The value for environment variable {} contains a NUL", name).into());
+    }
+    // SAFETY: the interpreter is single threaded, so nothing reads the
+    // environment concurrently. `cargo test` is not, which is why the test
+    // that sets a variable runs in a child process
+    unsafe { env::set_var(name, value) };
+    Ok(LiteralValue::Nil)
+}
+
+fn exit(_env: EnvRef, args: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let code = match args.first() {
+        Some(code) => number("exit", code)?,
+        None => 0.0,
+    };
+    if code.fract() != 0.0 || !(i32::MIN as f64..=i32::MAX as f64).contains(&code) {
+        return Err(format!("exit code must be an integer, got {}", code).into());
+    }
+    Err(RuntimeError::Exit { code: code as i32 })
+}
+
+fn cwd(_env: EnvRef, _args: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let dir =
+        env::current_dir().map_err(|e| format!("Could not read the working directory: {}", e))?;
+    Ok(LiteralValue::StringValue(
+        dir.to_string_lossy().into_owned(),
+    ))
+}
+
+// run(cmd, [args]) waits for the command and returns
+// {status, stdout, stderr}; status is nil when a signal ended it
+fn run(_env: EnvRef, args: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let program = string("run", &args[0])?;
+    let mut command = Command::new(program);
+    match args.get(1) {
+        None => {}
+        Some(LiteralValue::List(items)) => {
+            for item in items.borrow().iter() {
+                command.arg(string("run", item)?);
+            }
+        }
+        Some(other) => {
+            return Err(format!("run expected a list found {}", other.to_type()).into());
+        }
+    }
+    let output = command
+        .output()
+        .map_err(|e| format!("Could not run {}: {}", program, e))?;
+    let text =
+        |bytes: &[u8]| LiteralValue::StringValue(String::from_utf8_lossy(bytes).into_owned());
+    let status = match output.status.code() {
+        Some(code) => LiteralValue::Number(code as f64),
+        None => LiteralValue::Nil,
+    };
+    Ok(LiteralValue::map(BTreeMap::from([
+        ("status".to_string(), status),
+        ("stdout".to_string(), text(&output.stdout)),
+        ("stderr".to_string(), text(&output.stderr)),
+    ])))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(fun: Builtin, args: &[&str]) -> Result<LiteralValue, RuntimeError> {
+        let args: Vec<LiteralValue> = args
+            .iter()
+            .map(|s| LiteralValue::StringValue(s.to_string()))
+            .collect();
+        fun(Rc::new(RefCell::new(Environment::new())), &args)
+    }
+
+    fn sh(script: &str) -> Result<String, String> {
+        let args = LiteralValue::list(vec![
+            LiteralValue::StringValue("-c".to_string()),
+            LiteralValue::StringValue(script.to_string()),
+        ]);
+        run(
+            Rc::new(RefCell::new(Environment::new())),
+            &[LiteralValue::StringValue("sh".to_string()), args],
+        )
+        .map(|result| result.to_string())
+        .map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn run_reports_status_and_both_streams() {
+        assert_eq!(
+            sh("echo out; echo err >&2; exit 3"),
+            Ok(r#"{"status": 3, "stderr": "err\n", "stdout": "out\n"}"#.to_string())
+        );
+        assert!(sh("kill -9 $$").unwrap().starts_with(r#"{"status": nil"#));
+        let missing = call(run, &["nox-no-such-command"]).map_err(|e| e.to_string());
+        assert!(
+            missing
+                .unwrap_err()
+                .starts_with("[line 0] Could not run nox-no-such-command:")
+        );
+        assert!(call(run, &["sh", "-c"]).is_err());
+    }
+
+    #[test]
+    fn exit_unwinds_with_its_code() {
+        let code =
+            |args: &[LiteralValue]| match exit(Rc::new(RefCell::new(Environment::new())), args) {
+                Err(RuntimeError::Exit { code }) => Ok(code),
+                other => Err(other.map_err(|e| e.to_string())),
+            };
+        assert_eq!(code(&[]), Ok(0));
+        assert_eq!(code(&[LiteralValue::Number(-2.0)]), Ok(-2));
+        for bad in [1.5, 1e10, f64::NAN] {
+            assert!(code(&[LiteralValue::Number(bad)]).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn cwd_and_env_read_the_process_state() {
+        assert_eq!(
+            call(cwd, &[]).ok(),
+            Some(LiteralValue::StringValue(
+                env::current_dir().unwrap().display().to_string()
+            ))
+        );
+        assert_eq!(
+            call(get_env, &["NOX_OS_TEST_MISSING"]).ok(),
+            Some(LiteralValue::Nil)
+        );
+    }
+
+    #[test]
+    fn set_env_checks_the_name_and_the_value() {
+        let error = |args: &[&str]| call(set_env, args).map_err(|e| e.to_string()).err();
+        assert_eq!(
+            error(&["", "x"]),
+            Some("[line 0] Invalid environment variable name \"\"".to_string())
+        );
+        assert_eq!(
+            error(&["A=B", "x"]),
+            Some("[line 0] Invalid environment variable name \"A=B\"".to_string())
+        );
+        assert_eq!(
+            error(&["NOX_OS_TEST", "a\0b"]),
+            Some(
+                "[line 0] The value for environment variable NOX_OS_TEST contains a NUL"
+                    .to_string()
+            )
+        );
+    }
+
+    // other test threads read the environment, so the variable is set in a
+    // child copy of this test binary that runs only this test
+    #[test]
+    fn set_env_is_seen_by_later_reads_and_commands() {
+        if env::var_os("NOX_OS_CHILD").is_none() {
+            let output = Command::new(env::current_exe().unwrap())
+                .args([
+                    "--exact",
+                    "os::tests::set_env_is_seen_by_later_reads_and_commands",
+                    "--test-threads=1",
+                ])
+                .env("NOX_OS_CHILD", "1")
+                .output()
+                .unwrap();
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            assert!(output.status.success(), "{}", stdout);
+            assert!(stdout.contains("1 passed"), "{}", stdout);
+            return;
+        }
+        call(set_env, &["NOX_OS_TEST", "set"]).unwrap();
+        assert_eq!(
+            call(get_env, &["NOX_OS_TEST"]).ok(),
+            Some(LiteralValue::StringValue("set".to_string()))
+        );
+        assert!(
+            sh("printf %s \"$NOX_OS_TEST\"")
+                .unwrap()
+                .contains(r#""stdout": "set""#)
+        );
+    }
+}
@@ -1,15 +1,22 @@
-use std::{env, process::id, result, usize, vec};
+use std::path::Path;
 
 use crate::{
-    expr::{self, Expr, LiteralValue},
-    stmt::Stmt,
-    token::{self, Literal, Token},
+    expr::{Expr, LiteralValue},
+    scanner::Scanner,
+    stmt::{MatchArm, Param, Pattern, Stmt},
+    token::{Literal, Token},
     tokentype::TokenType,
 };
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
     is_error: bool,
+    // problems worth reporting that don't stop the program from running
+    pub warnings: Vec<String>,
+    // labels of the loops around the statement being parsed, innermost last
+    loops: Vec<Option<String>>,
+    // how many function bodies deep the parser is, for `return`
+    function_depth: usize,
 }
 
 impl Parser {
@@ -18,6 +25,9 @@ impl Parser {
             tokens,
             current: 0,
             is_error: false,
+            warnings: vec![],
+            loops: vec![],
+            function_depth: 0,
         }
     }
 
@@ -27,9 +37,9 @@ impl Parser {
         while !self.is_at_end() && !self.is_error {
             match self.declaration() {
                 Ok(s) => stmt.push(s),
-        Err(e) => {
-                    eprintln!("{}", e);
+                Err(e) => {
                     self.is_error = true;
+                    return Err(e);
                 }
             }
         }
@@ -37,7 +47,7 @@ impl Parser {
     }
 
     fn declaration(&mut self) -> Result<Stmt, String> {
-        if self.match_tokens(&[TokenType::VAR]) {
+        if self.match_tokens(&[TokenType::VAR, TokenType::CONST]) {
             match self.var_declaration() {
                 Ok(s) => Ok(s),
                 Err(e) => {
@@ -45,47 +55,86 @@ impl Parser {
                     Err(e)
                 }
             }
-        }else if  self.match_tokens(&[TokenType::FUN]){
+        } else if self.match_tokens(&[TokenType::FUN]) {
             self.funtion_decl("function")
-        }else {
+        } else {
             self.statement()
         }
     }
-    
-    fn funtion_decl(&mut self,kind:&str)->Result<Stmt,String>{
-        let token = self.consume(TokenType::IDENTIFIER, "Expected {kind} name")?;
-        self.consume(TokenType::LEFT_PAREN, "Expected  '(' after {kind} name");
-        let mut params = vec![];
+
+    fn funtion_decl(&mut self, kind: &str) -> Result<Stmt, String> {
+        let token = self.consume(TokenType::IDENTIFIER, &format!("Expected {kind} name"))?;
+        self.consume(
+            TokenType::LEFT_PAREN,
+            &format!("Expected  '(' after {kind} name"),
+        )?;
+        let mut params: Vec<Param> = vec![];
+        let mut rest = None;
         if !self.check(&TokenType::RIGHT_PAREN) {
             loop {
                 if params.len() >= 255 {
-                    return Err("cant have more than 255 params".to_string())
+                    return Err("cant have more than 255 params".to_string());
                 }
-                params.push(self.consume(TokenType::IDENTIFIER, "Expected param name")?);
-                if !self.match_tokens(&[TokenType::COMMA]){
+                if self.match_tokens(&[TokenType::DOT_DOT_DOT]) {
+                    rest = Some(self.consume(TokenType::IDENTIFIER, "Expected rest param name")?);
+                    break;
+                }
+                let name = self.consume(TokenType::IDENTIFIER, "Expected param name")?;
+                let default = if self.match_tokens(&[TokenType::EQUAL]) {
+                    Some(self.expression()?)
+                } else {
+                    None
+                };
+                if default.is_none() && params.last().is_some_and(|p| p.default.is_some()) {
+                    return Err(format!(
+                        "Param {} needs a default after params with defaults at line {}",
+                        name.lexeme, name.line
+                    ));
+                }
+                params.push(Param { name, default });
+                if !self.match_tokens(&[TokenType::COMMA]) {
                     break;
                 }
             }
         }
-        self.consume(TokenType::RIGHT_PAREN,"Expected ')' after params")?;
+        self.consume(
+            TokenType::RIGHT_PAREN,
+            "Expected ')' after params ('...rest' must come last)",
+        )?;
         self.consume(TokenType::LEFT_BRACE, "Expected '{' before block")?;
-        let Stmt::Block { stmts } = self.block()? else{
+        // a function body can't break out of the loop it is declared in
+        let enclosing_loops = std::mem::take(&mut self.loops);
+        self.function_depth += 1;
+        let body = self.block();
+        self.function_depth -= 1;
+        self.loops = enclosing_loops;
+        let Stmt::Block { stmts } = body? else {
             return Err("Unexpected issue".to_string());
         };
-        Ok(Stmt::Function { name:token ,  params, body: stmts })
+        Ok(Stmt::Function {
+            name: token,
+            params,
+            rest,
+            body: stmts,
+        })
     }
 
     fn var_declaration(&mut self) -> Result<Stmt, String> {
+        let constant = self.previous().token_type == TokenType::CONST;
         let token = self.consume(TokenType::IDENTIFIER, "Expected variable name")?;
 
-        let init;
-        if self.match_tokens(&[TokenType::EQUAL]) {
-            init = self.expression();
+        let init = if self.match_tokens(&[TokenType::EQUAL]) {
+            self.expression()
+        } else if constant {
+            return Err(format!(
+                "Constant {} needs a value at line {}",
+                token.lexeme, token.line
+            ));
         } else {
-            init = Ok(Expr::Literal {
+            Ok(Expr::Literal {
                 value: LiteralValue::Nil,
-            });
-        }
+            })
+        };
         self.consume(
             TokenType::SEMICOLON,
             "Expected ';' after variable declaration",
@@ -93,113 +142,501 @@ impl Parser {
         Ok(Stmt::Var {
             name: token,
             initializer: init?,
+            constant,
         })
     }
     fn statement(&mut self) -> Result<Stmt, String> {
-        if self.match_tokens(&[TokenType::PRINT]) {
-            self.print_stmt()
-        }else if self.match_tokens(&[TokenType::CONTINUE]) {
+        if self.check(&TokenType::IDENTIFIER) && self.check_next(&TokenType::COLON) {
+            let label = self.advance();
+            self.advance();
+            self.loop_statement(Some(label))
+        } else if self.check(&TokenType::FOR)
+            || self.check(&TokenType::WHILE)
+            || self.check(&TokenType::DO)
+        {
+            self.loop_statement(None)
+        } else if self.match_tokens(&[TokenType::CONTINUE]) {
             self.continue_statement()
-        }else if self.match_tokens(&[TokenType::BREAK]){
+        } else if self.match_tokens(&[TokenType::BREAK]) {
             self.break_stmt()
-        }else if self.match_tokens(&[TokenType::IF]) {
+        } else if self.match_tokens(&[TokenType::IF]) {
             self.if_statement()
-        }else if self.match_tokens(&[TokenType::FOR]) {
-           self.for_statement()
-        }else if self.match_tokens(&[TokenType::LEFT_BRACE]) {
+        } else if self.match_tokens(&[TokenType::LEFT_BRACE]) {
             self.block()
-        }else if self.match_tokens(&[TokenType::RETURN]){
+        } else if self.match_tokens(&[TokenType::RETURN]) {
             self.return_stmt()
-        } 
-        else if self.match_tokens(&[TokenType::WHILE]) {
-            self.while_stmt()
+        } else if self.match_tokens(&[TokenType::THROW]) {
+            self.throw_stmt()
+        } else if self.match_tokens(&[TokenType::TRY]) {
+            self.try_stmt()
+        } else if self.match_tokens(&[TokenType::IMPORT]) {
+            self.import_stmt()
+        } else if self.match_tokens(&[TokenType::FROM]) {
+            self.import_from_stmt()
+        } else if self.match_tokens(&[TokenType::MATCH]) {
+            self.match_stmt()
         } else {
             self.expression_stmt()
         }
     }
-    fn return_stmt(&mut self)->Result<Stmt,String>{
+    fn return_stmt(&mut self) -> Result<Stmt, String> {
         let token = self.previous();
+        if self.function_depth == 0 {
+            return Err(format!(
+                "'return' outside of a function at line {}",
+                token.line
+            ));
+        }
         let mut value = None;
-        if !self.check(&TokenType::SEMICOLON){
+        if !self.check(&TokenType::SEMICOLON) {
             value = Some(self.expression()?);
         }
-        self.consume(TokenType::SEMICOLON, "Expected ; after break")?;
+        self.consume(TokenType::SEMICOLON, "Expected ';' after return value")?;
         Ok(Stmt::Return { token, expr: value })
     }
 
+    // import "lib/math.nox" [as m];
+    fn import_stmt(&mut self) -> Result<Stmt, String> {
+        let keyword = self.previous();
+        let path = self.module_path("import")?;
+        let alias = if self.match_tokens(&[TokenType::AS]) {
+            self.consume(TokenType::IDENTIFIER, "Expected module name after as")?
+        } else {
+            let stem = Path::new(&path)
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default();
+            if !Scanner::is_identifier(&stem) {
+                return Err(format!(
+                    "Module {} needs a name, use 'as' at line {}",
+                    path, keyword.line
+                ));
+            }
+            Token::new(TokenType::IDENTIFIER, stem, None, keyword.line)
+        };
+        self.consume(TokenType::SEMICOLON, "Expected ';' after import")?;
+        Ok(Stmt::Import {
+            keyword,
+            path,
+            alias,
+        })
+    }
+
+    // from "lib/math.nox" import sqrt, pi;
+    fn import_from_stmt(&mut self) -> Result<Stmt, String> {
+        let keyword = self.previous();
+        let path = self.module_path("from")?;
+        self.consume(TokenType::IMPORT, "Expected import after module path")?;
+        let mut names = vec![];
+        loop {
+            names.push(self.consume(TokenType::IDENTIFIER, "Expected name to import")?);
+            if !self.match_tokens(&[TokenType::COMMA]) {
+                break;
+            }
+        }
+        self.consume(TokenType::SEMICOLON, "Expected ';' after import")?;
+        Ok(Stmt::FromImport {
+            keyword,
+            path,
+            names,
+        })
+    }
+
+    fn module_path(&mut self, keyword: &str) -> Result<String, String> {
+        let token = self.consume(
+            TokenType::STRING,
+            &format!("Expected module path after {}", keyword),
+        )?;
+        match token.literal {
+            Some(Literal::StringLiteral(path)) => Ok(path),
+            _ => Err(format!("Expected module path at line {}", token.line)),
+        }
+    }
+
+    fn throw_stmt(&mut self) -> Result<Stmt, String> {
+        let keyword = self.previous();
+        let value = self.expression()?;
+        self.consume(TokenType::SEMICOLON, "Expected ';' after thrown value")?;
+        Ok(Stmt::Throw { keyword, value })
+    }
+
+    fn try_stmt(&mut self) -> Result<Stmt, String> {
+        let line = self.previous().line;
+        self.consume(TokenType::LEFT_BRACE, "Expected '{' after try")?;
+        let Stmt::Block { stmts: body } = self.block()? else {
+            return Err("Expected a block".to_string());
+        };
+
+        let mut catch = None;
+        if self.match_tokens(&[TokenType::CATCH]) {
+            self.consume(TokenType::LEFT_PAREN, "Expected '(' after catch")?;
+            let name = self.consume(TokenType::IDENTIFIER, "Expected error name")?;
+            self.consume(TokenType::RIGHT_PAREN, "Expected ')' after error name")?;
+            self.consume(TokenType::LEFT_BRACE, "Expected '{' after catch clause")?;
+            let Stmt::Block { stmts } = self.block()? else {
+                return Err("Expected a block".to_string());
+            };
+            catch = Some((name, stmts));
+        }
+
+        let mut finally = None;
+        if self.match_tokens(&[TokenType::FINALLY]) {
+            self.consume(TokenType::LEFT_BRACE, "Expected '{' after finally")?;
+            let Stmt::Block { stmts } = self.block()? else {
+                return Err("Expected a block".to_string());
+            };
+            finally = Some(stmts);
+        }
+
+        if catch.is_none() && finally.is_none() {
+            return Err(format!(
+                "Expected catch or finally after try at line {}",
+                line
+            ));
+        }
+        Ok(Stmt::Try {
+            body,
+            catch,
+            finally,
+        })
+    }
+
+    // match (value) { pattern [if guard] => statement, ... }
+    fn match_stmt(&mut self) -> Result<Stmt, String> {
+        let keyword = self.previous();
+        self.consume(TokenType::LEFT_PAREN, "Expected '(' after match")?;
+        let value = self.expression()?;
+        self.consume(TokenType::RIGHT_PAREN, "Expected ')' after match value")?;
+        self.consume(TokenType::LEFT_BRACE, "Expected '{' before match arms")?;
+        let mut arms = vec![];
+        let mut lines = vec![];
+        while !self.check(&TokenType::RIGHT_BRACE) && !self.is_at_end() {
+            lines.push(self.peek().line);
+            let pattern = self.pattern()?;
+            let guard = if self.match_tokens(&[TokenType::IF]) {
+                Some(self.expression()?)
+            } else {
+                None
+            };
+            self.consume(TokenType::FAT_ARROW, "Expected '=>' after pattern")?;
+            let body = self.statement()?;
+            self.match_tokens(&[TokenType::COMMA]);
+            arms.push(MatchArm {
+                pattern,
+                guard,
+                body,
+            });
+        }
+        self.consume(TokenType::RIGHT_BRACE, "Expected '}' after match arms")?;
+        self.check_arms(&keyword, &arms, &lines);
+        Ok(Stmt::Match { value, arms })
+    }
+
+    // warns about arms that can never run and matches that may fall through;
+    // only catch-alls and literal `true`/`false` are understood
+    fn check_arms(&mut self, keyword: &Token, arms: &[MatchArm], lines: &[usize]) {
+        let mut seen: Vec<LiteralValue> = vec![];
+        let mut exhaustive = false;
+        for (arm, line) in arms.iter().zip(lines) {
+            let literals = match &arm.pattern {
+                Pattern::Literal(value) => vec![value.clone()],
+                Pattern::Alternatives(alts) => alts
+                    .iter()
+                    .filter_map(|p| match p {
+                        Pattern::Literal(value) => Some(value.clone()),
+                        _ => None,
+                    })
+                    .collect(),
+                _ => vec![],
+            };
+            let covered = matches!(arm.pattern, Pattern::Literal(_))
+                && literals.iter().all(|l| seen.contains(l));
+            if exhaustive || covered {
+                self.warnings
+                    .push(format!("[line {}] Warning: unreachable match arm", line));
+            }
+            if arm.guard.is_some() {
+                continue;
+            }
+            seen.extend(literals);
+            exhaustive |= arm.pattern.is_irrefutable()
+                || (seen.contains(&LiteralValue::True) && seen.contains(&LiteralValue::False));
+        }
+        if !exhaustive {
+            self.warnings.push(format!(
+                "[line {}] Warning: match is not exhaustive, add a '_' arm",
+                keyword.line
+            ));
+        }
+    }
+
+    fn pattern(&mut self) -> Result<Pattern, String> {
+        let first = self.single_pattern()?;
+        if !self.check(&TokenType::PIPE) {
+            return Ok(first);
+        }
+        let mut alternatives = vec![first];
+        while self.match_tokens(&[TokenType::PIPE]) {
+            alternatives.push(self.single_pattern()?);
+        }
+        if alternatives.iter().any(|p| p.binds_names()) {
+            return Err(format!(
+                "Alternative patterns can't bind names at line {}",
+                self.previous().line
+            ));
+        }
+        Ok(Pattern::Alternatives(alternatives))
+    }
+
+    fn single_pattern(&mut self) -> Result<Pattern, String> {
+        let token = self.advance();
+        match token.token_type {
+            TokenType::IDENTIFIER if token.lexeme == "_" => Ok(Pattern::Wildcard),
+            TokenType::IDENTIFIER => Ok(Pattern::Binding(token)),
+            TokenType::TRUE
+            | TokenType::FALSE
+            | TokenType::NIL
+            | TokenType::NUMBER
+            | TokenType::STRING => Ok(Pattern::Literal(LiteralValue::from_token(token))),
+            TokenType::MINUS => {
+                let number = self.consume(TokenType::NUMBER, "Expected number after '-'")?;
+                match LiteralValue::from_token(number) {
+                    LiteralValue::Number(x) => Ok(Pattern::Literal(LiteralValue::Number(-x))),
+                    _ => Err(format!("Invalid number pattern at line {}", token.line)),
+                }
+            }
+            TokenType::LEFT_BRACKET => self.list_pattern(),
+            TokenType::LEFT_BRACE => self.map_pattern(),
+            _ => Err(format!(
+                "Expected pattern found {} at line {}",
+                token.lexeme, token.line
+            )),
+        }
+    }
 
-    fn continue_statement(&mut self)->Result<Stmt,String>{
-       self.consume(TokenType::SEMICOLON, "Expected ; after break")?;
-       Ok(Stmt::Continue)
+    // [a, [b, _], ...rest]
+    fn list_pattern(&mut self) -> Result<Pattern, String> {
+        let mut items = vec![];
+        let mut rest = None;
+        while !self.check(&TokenType::RIGHT_BRACKET) && !self.is_at_end() {
+            if self.match_tokens(&[TokenType::DOT_DOT_DOT]) {
+                let name = if self.check(&TokenType::IDENTIFIER) && self.peek().lexeme != "_" {
+                    Some(self.advance())
+                } else {
+                    self.match_tokens(&[TokenType::IDENTIFIER]);
+                    None
+                };
+                rest = Some(name);
+                self.match_tokens(&[TokenType::COMMA]);
+                break;
+            }
+            items.push(self.pattern()?);
+            if !self.match_tokens(&[TokenType::COMMA]) {
+                break;
+            }
+        }
+        self.consume(
+            TokenType::RIGHT_BRACKET,
+            "Expected ']' after list pattern ('...' must come last)",
+        )?;
+        Ok(Pattern::List { items, rest })
     }
 
+    // {"key": pattern, name} where a bare name binds the value under that key
+    fn map_pattern(&mut self) -> Result<Pattern, String> {
+        let mut entries = vec![];
+        while !self.check(&TokenType::RIGHT_BRACE) && !self.is_at_end() {
+            let key = self.advance();
+            let name = match key.token_type {
+                TokenType::STRING => match LiteralValue::from_token(key.clone()) {
+                    LiteralValue::StringValue(s) => s,
+                    _ => unreachable!(),
+                },
+                TokenType::IDENTIFIER => key.lexeme.clone(),
+                _ => {
+                    return Err(format!(
+                        "Expected map key found {} at line {}",
+                        key.lexeme, key.line
+                    ));
+                }
+            };
+            let pattern = if self.match_tokens(&[TokenType::COLON]) {
+                self.pattern()?
+            } else if key.token_type == TokenType::IDENTIFIER {
+                Pattern::Binding(key)
+            } else {
+                return Err(format!("Expected ':' after map key at line {}", key.line));
+            };
+            entries.push((name, pattern));
+            if !self.match_tokens(&[TokenType::COMMA]) {
+                break;
+            }
+        }
+        self.consume(TokenType::RIGHT_BRACE, "Expected '}' after map pattern")?;
+        Ok(Pattern::Map(entries))
+    }
+
+    fn continue_statement(&mut self) -> Result<Stmt, String> {
+        let label = self.jump_label("continue")?;
+        Ok(Stmt::Continue { label })
+    }
 
-    fn break_stmt(&mut self)->Result<Stmt,String>{
-        self.consume(TokenType::SEMICOLON, "Expected ; after break")?;
-        Ok(Stmt::Break)
+    fn break_stmt(&mut self) -> Result<Stmt, String> {
+        let label = self.jump_label("break")?;
+        Ok(Stmt::Break { label })
     }
 
-    fn for_statement(&mut self)-> Result<Stmt,String>{
+    // the optional label after `break`/`continue`, which must name an
+    // enclosing loop
+    fn jump_label(&mut self, keyword: &str) -> Result<Option<String>, String> {
+        let line = self.previous().line;
+        let label = if self.check(&TokenType::IDENTIFIER) {
+            Some(self.advance().lexeme)
+        } else {
+            None
+        };
+        self.consume(
+            TokenType::SEMICOLON,
+            &format!("Expected ';' after {keyword}"),
+        )?;
+        if self.loops.is_empty() {
+            return Err(format!("'{}' outside of a loop at line {}", keyword, line));
+        }
+        if let Some(name) = &label
+            && !self.loops.contains(&label)
+        {
+            return Err(format!("Unknown loop label {} at line {}", name, line));
+        }
+        Ok(label)
+    }
+
+    // `for`, `while` and `do` loops, optionally labelled `name: while (...)`
+    fn loop_statement(&mut self, label: Option<Token>) -> Result<Stmt, String> {
+        if let Some(token) = &label
+            && self.loops.contains(&Some(token.lexeme.clone()))
+        {
+            return Err(format!(
+                "Label {} is already used by an enclosing loop at line {}",
+                token.lexeme, token.line
+            ));
+        }
+        let line = self.peek().line;
+        let label = label.map(|t| t.lexeme);
+        if self.match_tokens(&[TokenType::FOR]) {
+            self.for_statement(label)
+        } else if self.match_tokens(&[TokenType::WHILE]) {
+            self.while_stmt(label)
+        } else if self.match_tokens(&[TokenType::DO]) {
+            self.do_while_stmt(label)
+        } else {
+            Err(format!("Expected a loop after label at line {}", line))
+        }
+    }
+
+    fn loop_body(&mut self, label: &Option<String>) -> Result<Stmt, String> {
+        self.loops.push(label.clone());
+        let body = self.statement();
+        self.loops.pop();
+        body
+    }
+
+    fn for_statement(&mut self, label: Option<String>) -> Result<Stmt, String> {
         // expr statemet | var var_declaration
-        self.consume(TokenType::LEFT_PAREN,"Expected '('  after for")?;
-        let statement_declaration =
-        if  self.match_tokens(&[TokenType::SEMICOLON]){
+        self.consume(TokenType::LEFT_PAREN, "Expected '('  after for")?;
+        if self.check(&TokenType::IDENTIFIER)
+            && (self.check_next(&TokenType::IN) || self.check_next(&TokenType::COMMA))
+        {
+            return self.for_in_statement(label);
+        }
+        let statement_declaration = if self.match_tokens(&[TokenType::SEMICOLON]) {
             None
-        }else if self.match_tokens(&[TokenType::VAR]){
+        } else if self.match_tokens(&[TokenType::VAR]) {
             Some(self.var_declaration()?)
-        }else{
+        } else {
             Some(self.expression_stmt()?)
         };
 
-        let mut condition = if self.check(&TokenType::SEMICOLON){
+        let mut condition = if self.check(&TokenType::SEMICOLON) {
             None
-        }else{
+        } else {
             Some(self.expression()?)
         };
 
         self.consume(TokenType::SEMICOLON, "Expected ';' after loop condition")?;
-        let increment= if self.check(&TokenType::RIGHT_PAREN){
-                 None
-        }else{
-
-                Some(self.expression()?)
+        let increment = if self.check(&TokenType::RIGHT_PAREN) {
+            None
+        } else {
+            Some(self.expression()?)
         };
 
-        let mut body;
         self.consume(TokenType::RIGHT_PAREN, "expected ')' after clause")?;
-        self.consume(TokenType::LEFT_BRACE, "expected '{' after for clause")?;
-        let  Stmt::Block {mut stmts }= self.block()? else{
-            return Err("Expeccted a block".to_string());
-        };
-        if !increment.is_none() {
-            stmts.push(Stmt::Block { 
-                         stmts: vec![
-                            Stmt::Expression { expression:increment.unwrap()}
-                         ]
-                    }
-            );
+        let block = self.loop_body(&label)?;
+        if condition.is_none() {
+            condition = Some(Expr::Literal {
+                value: LiteralValue::True,
+            })
         }
-        if condition.is_none(){
-            condition = Some(Expr::Literal { value: LiteralValue::True })
+        let body = Stmt::WHILE {
+            condition: condition.unwrap(),
+            block: Box::new(block),
+            increment,
+            label,
+        };
+        if let Some(init) = statement_declaration {
+            Ok(Stmt::Block {
+                stmts: vec![init, body],
+            })
+        } else {
+            Ok(body)
         }
-        body = Stmt::WHILE { condition: condition.unwrap(), block: Box::new(Stmt::Block {  stmts }) }; 
-        if let Some(init)=statement_declaration{
+    }
 
-            Ok(Stmt::Block { stmts: vec![init,body] })
-        }else {
-          Ok(body)
+    fn for_in_statement(&mut self, label: Option<String>) -> Result<Stmt, String> {
+        let mut names = vec![self.advance()];
+        if self.match_tokens(&[TokenType::COMMA]) {
+            names.push(self.consume(TokenType::IDENTIFIER, "Expected name after ','")?);
         }
+        let keyword = self.consume(TokenType::IN, "Expected 'in' after loop variables")?;
+        let iterable = self.expression()?;
+        self.consume(TokenType::RIGHT_PAREN, "expected ')' after clause")?;
+        let body = self.loop_body(&label)?;
+        Ok(Stmt::ForIn {
+            names,
+            keyword,
+            iterable,
+            body: Box::new(body),
+            label,
+        })
     }
 
-    fn while_stmt(&mut self) -> Result<Stmt, String> {
+    fn while_stmt(&mut self, label: Option<String>) -> Result<Stmt, String> {
         self.consume(TokenType::LEFT_PAREN, "( Expected after while")?;
         let expr = self.expression()?;
         self.consume(TokenType::RIGHT_PAREN, ") Expected after statement")?;
-        self.consume(TokenType::LEFT_BRACE, "{ Expected before start of block")?;
-        let block = self.block()?;
+        let block = self.loop_body(&label)?;
         Ok(Stmt::WHILE {
             condition: expr,
             block: Box::new(block),
+            increment: None,
+            label,
+        })
+    }
+
+    // do body while (condition);
+    fn do_while_stmt(&mut self, label: Option<String>) -> Result<Stmt, String> {
+        let body = self.loop_body(&label)?;
+        self.consume(TokenType::WHILE, "Expected 'while' after do body")?;
+        self.consume(TokenType::LEFT_PAREN, "( Expected after while")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RIGHT_PAREN, ") Expected after statement")?;
+        self.consume(
+            TokenType::SEMICOLON,
+            "Expected ';' after do-while condition",
+        )?;
+        Ok(Stmt::DoWhile {
+            body: Box::new(body),
+            condition,
+            label,
         })
     }
 
@@ -207,12 +644,11 @@ impl Parser {
         self.consume(TokenType::LEFT_PAREN, "Expected '(' after if")?;
         let expression = self.expression()?;
         self.consume(TokenType::RIGHT_PAREN, "Expected ')' after expression")?;
-        self.consume(TokenType::LEFT_BRACE, "Expected { before start of block ")?;
-        let block = Box::new(self.block()?);
+        let block = Box::new(self.statement()?);
 
+        // a dangling else binds to the nearest if
         let els_stmt = if self.match_tokens(&[TokenType::ELSE]) {
-            self.consume(TokenType::LEFT_BRACE, "Expected { before start of block ")?;
-            Some(Box::new(self.block()?))
+            Some(Box::new(self.statement()?))
         } else {
             None
         };
@@ -232,15 +668,6 @@ impl Parser {
         Ok(Stmt::Block { stmts: statements })
     }
 
-    fn print_stmt(&mut self) -> Result<Stmt, String> {
-        self.consume(TokenType::LEFT_PAREN, "Expected '(' before value")?;
-        let expr = self.expression()?;
-        self.consume(TokenType::RIGHT_PAREN, "Expected ')' after value")?;
-        self.consume(TokenType::SEMICOLON, "Expected after value';' ")?;
-
-        Ok(Stmt::Print { expression: expr })
-    }
-
     fn expression_stmt(&mut self) -> Result<Stmt, String> {
         let expr = self.expression()?;
         self.consume(TokenType::SEMICOLON, "Expected  ';' after expression")?;
@@ -252,7 +679,7 @@ impl Parser {
     }
 
     fn assignment(&mut self) -> Result<Expr, String> {
-        let expr = self.or()?;
+        let expr = self.conditional()?;
         if self.match_tokens(&[TokenType::EQUAL]) {
             let value = self.assignment()?;
             match expr {
@@ -260,13 +687,104 @@ impl Parser {
                     name,
                     value: Box::from(value),
                 }),
+                Expr::Index {
+                    object,
+                    bracket,
+                    index,
+                } => Ok(Expr::IndexSet {
+                    object,
+                    bracket,
+                    index,
+                    value: Box::from(value),
+                }),
                 _ => Err("Invalid assignment target".to_string()),
             }
+        } else if self.match_tokens(&[
+            TokenType::PLUS_EQUAL,
+            TokenType::MINUS_EQUAL,
+            TokenType::STAR_EQUAL,
+            TokenType::SLASH_EQUAL,
+            TokenType::MODULUS_EQUAL,
+        ]) {
+            let operator = self.previous();
+            let value = self.assignment()?;
+            let target = Self::assignable(expr, &operator)?;
+            let token_type = match operator.token_type {
+                TokenType::PLUS_EQUAL => TokenType::PLUS,
+                TokenType::MINUS_EQUAL => TokenType::MINUS,
+                TokenType::STAR_EQUAL => TokenType::STAR,
+                TokenType::SLASH_EQUAL => TokenType::SLASH,
+                _ => TokenType::Modulus,
+            };
+            Ok(Expr::CompoundAssign {
+                target: Box::new(target),
+                operator: Token::new(
+                    token_type,
+                    operator.lexeme.trim_end_matches('=').to_string(),
+                    None,
+                    operator.line,
+                ),
+                value: Box::new(value),
+            })
         } else {
             Ok(expr)
         }
     }
 
+    fn assignable(expr: Expr, operator: &Token) -> Result<Expr, String> {
+        match expr {
+            Expr::Variable { .. } | Expr::Index { .. } => Ok(expr),
+            _ => Err(format!(
+                "Invalid target for {} at line {}",
+                operator.lexeme, operator.line
+            )),
+        }
+    }
+
+    // `++x` / `x--` become an increment by one with the plain operator
+    fn increment(target: Expr, operator: &Token, prefix: bool) -> Result<Expr, String> {
+        let target = Self::assignable(target, operator)?;
+        let (token_type, lexeme) = match operator.token_type {
+            TokenType::PLUS_PLUS => (TokenType::PLUS, "+"),
+            _ => (TokenType::MINUS, "-"),
+        };
+        Ok(Expr::Increment {
+            target: Box::new(target),
+            operator: Token::new(token_type, lexeme.to_string(), None, operator.line),
+            prefix,
+        })
+    }
+
+    fn conditional(&mut self) -> Result<Expr, String> {
+        let expr = self.coalesce()?;
+        if self.match_tokens(&[TokenType::QUESTION]) {
+            let then = self.expression()?;
+            self.consume(TokenType::COLON, "Expected ':' in conditional expression")?;
+            let els = self.conditional()?;
+            Ok(Expr::Ternary {
+                condition: Box::new(expr),
+                then: Box::new(then),
+                els: Box::new(els),
+            })
+        } else {
+            Ok(expr)
+        }
+    }
+
+    fn coalesce(&mut self) -> Result<Expr, String> {
+        let mut expr = self.or()?;
+        while self.match_tokens(&[TokenType::QUESTION_QUESTION]) {
+            let operator = self.previous();
+            let right = self.or()?;
+            expr = Expr::Logical {
+                expression: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            }
+        }
+        Ok(expr)
+    }
+
     fn or(&mut self) -> Result<Expr, String> {
         let mut expr = self.and()?;
         while self.match_tokens(&[TokenType::OR]) {
@@ -318,10 +836,16 @@ impl Parser {
                 TokenType::CLASS
                 | TokenType::FUN
                 | TokenType::VAR
+                | TokenType::CONST
                 | TokenType::FOR
                 | TokenType::IF
                 | TokenType::WHILE
-                | TokenType::PRINT
+                | TokenType::DO
+                | TokenType::TRY
+                | TokenType::THROW
+                | TokenType::IMPORT
+                | TokenType::FROM
+                | TokenType::MATCH
                 | TokenType::RETURN => return,
                 _ => (),
             }
@@ -329,7 +853,7 @@ impl Parser {
         }
     }
     fn comparision(&mut self) -> Result<Expr, String> {
-        let mut expr = self.term()?;
+        let mut expr = self.range()?;
 
         while self.match_tokens(&[
             TokenType::GREATER_EQUAL,
@@ -337,6 +861,82 @@ impl Parser {
             TokenType::LESS,
             TokenType::LESS_EQUAL,
         ]) {
+            let operator = self.previous();
+            let right = self.range()?;
+            expr = Expr::Binary {
+                left: Box::from(expr),
+                operator,
+                right: Box::from(right),
+            }
+        }
+        Ok(expr)
+    }
+
+    // `a..b` doesn't chain: `0..n-1` works, `0..1..2` doesn't
+    fn range(&mut self) -> Result<Expr, String> {
+        let expr = self.bit_or()?;
+        if self.match_tokens(&[TokenType::DOT_DOT]) {
+            let operator = self.previous();
+            let right = self.bit_or()?;
+            Ok(Expr::Binary {
+                left: Box::from(expr),
+                operator,
+                right: Box::from(right),
+            })
+        } else {
+            Ok(expr)
+        }
+    }
+
+    fn bit_or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.bit_xor()?;
+
+        while self.match_tokens(&[TokenType::PIPE]) {
+            let operator = self.previous();
+            let right = self.bit_xor()?;
+            expr = Expr::Binary {
+                left: Box::from(expr),
+                operator,
+                right: Box::from(right),
+            }
+        }
+        Ok(expr)
+    }
+
+    fn bit_xor(&mut self) -> Result<Expr, String> {
+        let mut expr = self.bit_and()?;
+
+        while self.match_tokens(&[TokenType::CARET]) {
+            let operator = self.previous();
+            let right = self.bit_and()?;
+            expr = Expr::Binary {
+                left: Box::from(expr),
+                operator,
+                right: Box::from(right),
+            }
+        }
+        Ok(expr)
+    }
+
+    fn bit_and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.shift()?;
+
+        while self.match_tokens(&[TokenType::AMPERSAND]) {
+            let operator = self.previous();
+            let right = self.shift()?;
+            expr = Expr::Binary {
+                left: Box::from(expr),
+                operator,
+                right: Box::from(right),
+            }
+        }
+        Ok(expr)
+    }
+
+    fn shift(&mut self) -> Result<Expr, String> {
+        let mut expr = self.term()?;
+
+        while self.match_tokens(&[TokenType::LESS_LESS, TokenType::GREATER_GREATER]) {
             let operator = self.previous();
             let right = self.term()?;
             expr = Expr::Binary {
@@ -347,6 +947,7 @@ impl Parser {
         }
         Ok(expr)
     }
+
     fn term(&mut self) -> Result<Expr, String> {
         let mut expr = self.factor()?;
 
@@ -365,7 +966,12 @@ impl Parser {
     fn factor(&mut self) -> Result<Expr, String> {
         let mut expr = self.unary()?;
 
-        while self.match_tokens(&[TokenType::STAR, TokenType::SLASH,TokenType::Modulus]) {
+        while self.match_tokens(&[
+            TokenType::STAR,
+            TokenType::SLASH,
+            TokenType::TILDE_SLASH,
+            TokenType::Modulus,
+        ]) {
             let operator = self.previous();
             let right = self.unary()?;
             expr = Expr::Binary {
@@ -378,77 +984,173 @@ impl Parser {
     }
 
     fn unary(&mut self) -> Result<Expr, String> {
-        if self.match_tokens(&[TokenType::BANG, TokenType::MINUS]) {
+        if self.match_tokens(&[TokenType::BANG, TokenType::MINUS, TokenType::TILDE]) {
             let operator = self.previous();
             let right = self.unary()?;
             Ok(Expr::Unary {
                 operator,
                 right: Box::from(right),
             })
+        } else if self.match_tokens(&[TokenType::PLUS_PLUS, TokenType::MINUS_MINUS]) {
+            let operator = self.previous();
+            let target = self.unary()?;
+            Self::increment(target, &operator, true)
+        } else {
+            self.power()
+        }
+    }
+
+    // right associative and tighter than unary minus: -2 ** 2 == -4
+    fn power(&mut self) -> Result<Expr, String> {
+        let expr = self.postfix()?;
+        if self.match_tokens(&[TokenType::STAR_STAR]) {
+            let operator = self.previous();
+            let right = self.unary()?;
+            Ok(Expr::Binary {
+                left: Box::from(expr),
+                operator,
+                right: Box::from(right),
+            })
         } else {
-            self.call()
+            Ok(expr)
+        }
+    }
+
+    fn postfix(&mut self) -> Result<Expr, String> {
+        let expr = self.call()?;
+        if self.match_tokens(&[TokenType::PLUS_PLUS, TokenType::MINUS_MINUS]) {
+            let operator = self.previous();
+            Self::increment(expr, &operator, false)
+        } else {
+            Ok(expr)
         }
     }
 
-    fn call(&mut self)->Result<Expr,String>{
+    fn call(&mut self) -> Result<Expr, String> {
         let mut expr = self.primary()?;
 
         loop {
-            if self.match_tokens(&[TokenType::LEFT_PAREN]){
+            if self.match_tokens(&[TokenType::LEFT_PAREN]) {
                 expr = self.finishCall(expr)?;
-            }else {
+            } else if self.match_tokens(&[TokenType::DOT]) {
+                let name =
+                    self.consume(TokenType::IDENTIFIER, "Expected property name after '.'")?;
+                expr = Expr::Get {
+                    object: Box::new(expr),
+                    name,
+                };
+            } else if self.match_tokens(&[TokenType::LEFT_BRACKET]) {
+                let bracket = self.previous();
+                let index = self.expression()?;
+                self.consume(TokenType::RIGHT_BRACKET, "Expected ']' after index")?;
+                expr = Expr::Index {
+                    object: Box::new(expr),
+                    bracket,
+                    index: Box::new(index),
+                };
+            } else {
                 break;
             }
         }
         Ok(expr)
     }
 
-    fn finishCall(&mut self,callie:Expr)->Result<Expr,String>{
+    fn finishCall(&mut self, callie: Expr) -> Result<Expr, String> {
         let mut args = vec![];
+        let mut named = vec![];
         if !self.check(&TokenType::RIGHT_PAREN) {
             loop {
-                if args.len() >= 255{
+                if args.len() + named.len() >= 255 {
                     return Err("Can't have more than 255 args".to_string());
                 }
-                args.push(self.expression()?);
-                if !self.match_tokens(&[TokenType::COMMA]){
-                    break ;
+                if self.check(&TokenType::IDENTIFIER) && self.check_next(&TokenType::COLON) {
+                    let key = self.advance();
+                    self.advance();
+                    named.push((key, self.expression()?));
+                } else if let Some((key, _)) = named.last() {
+                    return Err(format!(
+                        "Positional argument after named argument {} at line {}",
+                        key.lexeme, key.line
+                    ));
+                } else {
+                    args.push(self.expression()?);
+                }
+                if !self.match_tokens(&[TokenType::COMMA]) {
+                    break;
                 }
             }
         }
-        let token = self.previous();
         let token = self.consume(TokenType::RIGHT_PAREN, "Expected ')' after args")?;
-        Ok(Expr::Call { callie : Box::new(callie), paren: token, args })
+        Ok(Expr::Call {
+            callie: Box::new(callie),
+            paren: token,
+            args,
+            named,
+        })
     }
 
     fn primary(&mut self) -> Result<Expr, String> {
         let token = self.peek();
-        let result;
-        match token.token_type {
+        let result = match token.token_type {
             TokenType::LEFT_PAREN => {
                 self.advance();
                 let expr = self.expression()?;
                 self.consume(TokenType::RIGHT_PAREN, "Expected ')'")?;
-                result = Expr::Grouping {
+                Expr::Grouping {
                     expression: Box::from(expr),
                 }
             }
-              TokenType::FALSE
+            TokenType::FALSE
             | TokenType::TRUE
             | TokenType::NIL
             | TokenType::NUMBER
             | TokenType::STRING => {
                 self.advance();
-                result = Expr::Literal {
+                Expr::Literal {
                     value: LiteralValue::from_token(token),
                 }
             }
             TokenType::IDENTIFIER => {
                 self.advance();
-                result = Expr::Variable {
+                Expr::Variable {
                     name: self.previous(),
                 }
             }
+            TokenType::LEFT_BRACKET => {
+                self.advance();
+                let mut items = vec![];
+                while !self.check(&TokenType::RIGHT_BRACKET) && !self.is_at_end() {
+                    items.push(self.expression()?);
+                    if !self.match_tokens(&[TokenType::COMMA]) {
+                        break;
+                    }
+                }
+                self.consume(TokenType::RIGHT_BRACKET, "Expected ']' after list items")?;
+                Expr::List { items }
+            }
+            // {"key": value}; a bare name before ':' is a string key
+            TokenType::LEFT_BRACE => {
+                let brace = self.advance();
+                let mut entries = vec![];
+                while !self.check(&TokenType::RIGHT_BRACE) && !self.is_at_end() {
+                    let key = if self.check(&TokenType::IDENTIFIER)
+                        && self.check_next(&TokenType::COLON)
+                    {
+                        Expr::Literal {
+                            value: LiteralValue::StringValue(self.advance().lexeme),
+                        }
+                    } else {
+                        self.expression()?
+                    };
+                    self.consume(TokenType::COLON, "Expected ':' after map key")?;
+                    entries.push((key, self.expression()?));
+                    if !self.match_tokens(&[TokenType::COMMA]) {
+                        break;
+                    }
+                }
+                self.consume(TokenType::RIGHT_BRACE, "Expected '}' after map entries")?;
+                Expr::Map { brace, entries }
+            }
             _ => {
                 let token = self.peek();
                 return Err(format!(
@@ -456,7 +1158,7 @@ impl Parser {
                     token.line, token.lexeme
                 ));
             }
-        }
+        };
 
         Ok(result)
     }
@@ -468,7 +1170,7 @@ impl Parser {
             let token = self.previous();
             Ok(token)
         } else {
-            return Err(format!("{} at line {}", msg.to_string(), token.line));
+            Err(format!("{} at line {}", msg, token.line))
         }
     }
 
@@ -491,6 +1193,12 @@ impl Parser {
         }
     }
 
+    fn check_next(&self, tk_type: &TokenType) -> bool {
+        self.tokens
+            .get(self.current + 1)
+            .is_some_and(|t| t.token_type == *tk_type)
+    }
+
     fn advance(&mut self) -> Token {
         if !self.is_at_end() {
             self.current += 1;
@@ -505,7 +1213,7 @@ impl Parser {
         self.tokens.get(self.current).unwrap().clone()
     }
     fn previous(&self) -> Token {
-        self.tokens.get(self.current - 1 as usize).unwrap().clone()
+        self.tokens.get(self.current - 1).unwrap().clone()
     }
 }
 /*
@@ -0,0 +1,254 @@
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    builtins::number,
+    environment::Environment,
+    expr::{Arity, LiteralValue},
+};
+
+// xoshiro256**, seeded through splitmix64 as its authors recommend
+pub struct Rng {
+    state: [u64; 4],
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        let mut x = seed;
+        let mut splitmix = || {
+            x = x.wrapping_add(0x9e3779b97f4a7c15);
+            let mut z = x;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            z ^ (z >> 31)
+        };
+        Self {
+            state: [splitmix(), splitmix(), splitmix(), splitmix()],
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let s = &mut self.state;
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        result
+    }
+
+    // uniform in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    // uniform in [0, n), rejecting the uneven tail so no value is favoured
+    pub fn below(&mut self, n: u64) -> u64 {
+        let limit = u64::MAX - u64::MAX % n;
+        loop {
+            let x = self.next_u64();
+            if x < limit {
+                return x % n;
+            }
+        }
+    }
+}
+
+type Method = fn(&mut Rng, &[LiteralValue]) -> Result<LiteralValue, String>;
+
+// every function shares the module's generator, so `seed` affects them all
+pub fn module() -> Environment {
+    let seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0);
+    let rng = Rc::new(RefCell::new(Rng::new(seed)));
+    let mut env = Environment::new();
+    let functions: [(&str, Arity, Method); 5] = [
+        ("random", Arity::exact(0), |rng, _| {
+            Ok(LiteralValue::Number(rng.next_f64()))
+        }),
+        ("randint", Arity::exact(2), randint),
+        ("choice", Arity::exact(1), choice),
+        ("shuffle", Arity::exact(1), shuffle),
+        ("seed", Arity::exact(1), |rng, args| {
+            let seed = number("seed", &args[0])?;
+            if !seed.is_finite() {
+                return Err(format!("seed expected a finite number found {}", seed));
+            }
+            *rng = Rng::new(seed.to_bits());
+            Ok(LiteralValue::Nil)
+        }),
+    ];
+    for (name, arity, fun) in functions {
+        let rng = rng.clone();
+        env.define_const(
+            name,
+            LiteralValue::Callable {
+                name: name.to_string(),
+                arity,
+                params: Rc::from([]),
+                keywords: Rc::from([]),
+                fun: Rc::new(move |_env, args| Ok(fun(&mut rng.borrow_mut(), args)?)),
+            },
+        );
+    }
+    env
+}
+
+// randint(a, b) includes both ends
+fn randint(rng: &mut Rng, args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    let low = number("randint", &args[0])?;
+    let high = number("randint", &args[1])?;
+    // past 2^53 not every integer is a Number anymore
+    let limit = (1u64 << 53) as f64;
+    if low.fract() != 0.0 || high.fract() != 0.0 || low.abs() > limit || high.abs() > limit {
+        return Err(format!(
+            "randint expected integers found {} and {}",
+            low, high
+        ));
+    }
+    if low > high {
+        return Err(format!(
+            "randint bounds {} and {} are out of order",
+            low, high
+        ));
+    }
+    let span = (high - low) as u64 + 1;
+    Ok(LiteralValue::Number(low + rng.below(span) as f64))
+}
+
+fn choice(rng: &mut Rng, args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    let LiteralValue::List(items) = &args[0] else {
+        return Err(format!(
+            "choice expected a list found {}",
+            args[0].to_type()
+        ));
+    };
+    let items = items.borrow();
+    if items.is_empty() {
+        return Err("choice from an empty list".to_string());
+    }
+    Ok(items[rng.below(items.len() as u64) as usize].clone())
+}
+
+// shuffles the list in place
+fn shuffle(rng: &mut Rng, args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    let LiteralValue::List(items) = &args[0] else {
+        return Err(format!(
+            "shuffle expected a list found {}",
+            args[0].to_type()
+        ));
+    };
+    let mut items = items.borrow_mut();
+    for i in (1..items.len()).rev() {
+        let j = rng.below(i as u64 + 1) as usize;
+        items.swap(i, j);
+    }
+    Ok(LiteralValue::Nil)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbers(xs: &[f64]) -> Vec<LiteralValue> {
+        xs.iter().map(|x| LiteralValue::Number(*x)).collect()
+    }
+
+    #[test]
+    fn known_seeds_give_known_sequences() {
+        // the state is the published splitmix64 sequence for seed 0
+        let mut rng = Rng::new(0);
+        assert_eq!(
+            rng.state,
+            [
+                0xe220a8397b1dcdaf,
+                0x6e789e6aa1b965f4,
+                0x06c45d188009454f,
+                0xf88bb8a8724c81ec
+            ]
+        );
+        let outputs: Vec<u64> = (0..3).map(|_| rng.next_u64()).collect();
+        assert_eq!(
+            outputs,
+            [0x99ec5f36cb75f2b4, 0xbf6e1f784956452a, 0x1a5f849d4933e6e0]
+        );
+        let mut rng = Rng::new(1);
+        let outputs: Vec<u64> = (0..3).map(|_| rng.next_u64()).collect();
+        assert_eq!(
+            outputs,
+            [0xb3f2af6d0fc710c5, 0x853b559647364cea, 0x92f89756082a4514]
+        );
+        assert_eq!(
+            Rng::new(0).next_f64(),
+            (0x99ec5f36cb75f2b4u64 >> 11) as f64 / 2f64.powi(53)
+        );
+    }
+
+    #[test]
+    fn ranges_stay_inside_their_bounds() {
+        let mut rng = Rng::new(7);
+        let mut seen = [false; 3];
+        for _ in 0..200 {
+            seen[rng.below(3) as usize] = true;
+            let f = rng.next_f64();
+            assert!((0.0..1.0).contains(&f));
+            let x = randint(&mut rng, &numbers(&[-2.0, 2.0])).unwrap();
+            let LiteralValue::Number(x) = x else {
+                panic!("randint returned {}", x);
+            };
+            assert!((-2.0..=2.0).contains(&x) && x.fract() == 0.0);
+        }
+        assert_eq!(seen, [true; 3]);
+        assert_eq!(
+            randint(&mut rng, &numbers(&[5.0, 5.0])),
+            Ok(LiteralValue::Number(5.0))
+        );
+    }
+
+    #[test]
+    fn bad_arguments_are_errors() {
+        let mut rng = Rng::new(0);
+        assert_eq!(
+            randint(&mut rng, &numbers(&[3.0, 1.0])),
+            Err("randint bounds 3 and 1 are out of order".to_string())
+        );
+        assert_eq!(
+            randint(&mut rng, &numbers(&[0.0, 1.5])),
+            Err("randint expected integers found 0 and 1.5".to_string())
+        );
+        assert!(randint(&mut rng, &numbers(&[0.0, 1e16])).is_err());
+        assert_eq!(
+            choice(&mut rng, &[LiteralValue::list(vec![])]),
+            Err("choice from an empty list".to_string())
+        );
+        assert!(choice(&mut rng, &[LiteralValue::Nil]).is_err());
+        assert!(shuffle(&mut rng, &[LiteralValue::Number(1.0)]).is_err());
+    }
+
+    #[test]
+    fn shuffle_keeps_every_item() {
+        let list = LiteralValue::list(numbers(&[1.0, 2.0, 3.0, 4.0, 5.0]));
+        shuffle(&mut Rng::new(3), std::slice::from_ref(&list)).unwrap();
+        let LiteralValue::List(items) = &list else {
+            unreachable!()
+        };
+        let mut items: Vec<f64> = items
+            .borrow()
+            .iter()
+            .map(|v| match v {
+                LiteralValue::Number(x) => *x,
+                _ => panic!("shuffle changed an item"),
+            })
+            .collect();
+        items.sort_by(f64::total_cmp);
+        assert_eq!(items, [1.0, 2.0, 3.0, 4.0, 5.0]);
+    }
+}
@@ -0,0 +1,615 @@
+use std::collections::HashMap;
+
+use crate::{
+    environment::Environment,
+    expr::Expr,
+    stmt::{Pattern, Stmt},
+    token::Token,
+};
+
+struct Binding {
+    constant: bool,
+    // false while the variable's own initializer is being resolved
+    defined: bool,
+    used: bool,
+    line: usize,
+    // locals declared with var/const/fun are reported when never read
+    warn_unused: bool,
+    // seeded from the starting environment, such as an earlier REPL line
+    inherited: bool,
+}
+
+#[derive(Default)]
+struct Scope {
+    bindings: HashMap<String, Binding>,
+    // names the scope's statements declare further down, and whether a
+    // function body already read them
+    hoisted: HashMap<String, bool>,
+}
+
+// Static checks run between parsing and interpreting. Its scopes mirror the
+// environments the interpreter will create, starting from the ones that
+// already exist (builtins, a module's globals, earlier REPL lines).
+pub struct Resolver {
+    scopes: Vec<Scope>,
+    // index of the program's own top-level scope; deeper scopes are local
+    top: usize,
+    // index of the first scope of each function being resolved
+    functions: Vec<usize>,
+    warnings: Vec<String>,
+}
+
+impl Resolver {
+    pub fn new(env: &Environment) -> Self {
+        let mut scopes = vec![];
+        Self::collect(env, &mut scopes);
+        Self {
+            top: scopes.len() - 1,
+            scopes,
+            functions: vec![],
+            warnings: vec![],
+        }
+    }
+
+    fn collect(env: &Environment, scopes: &mut Vec<Scope>) {
+        if let Some(parent) = &env.enclosing {
+            Self::collect(&parent.borrow(), scopes);
+        }
+        let bindings = env
+            .values
+            .keys()
+            .map(|name| {
+                let binding = Binding {
+                    constant: env.is_constant(name),
+                    defined: true,
+                    used: true,
+                    line: 0,
+                    warn_unused: false,
+                    inherited: true,
+                };
+                (name.clone(), binding)
+            })
+            .collect();
+        scopes.push(Scope {
+            bindings,
+            hoisted: HashMap::new(),
+        });
+    }
+
+    // checks a whole program and returns its warnings
+    pub fn resolve(mut self, stmts: &[Stmt]) -> Result<Vec<String>, String> {
+        self.hoist(stmts);
+        self.stmts(stmts)?;
+        Ok(self.warnings)
+    }
+
+    fn stmts(&mut self, stmts: &[Stmt]) -> Result<(), String> {
+        for stmt in stmts {
+            self.stmt(stmt)?;
+        }
+        Ok(())
+    }
+
+    fn hoist(&mut self, stmts: &[Stmt]) {
+        let scope = self.scopes.last_mut().unwrap();
+        for stmt in stmts {
+            let names = match stmt {
+                Stmt::Var { name, .. } | Stmt::Function { name, .. } => vec![name],
+                Stmt::Import { alias, .. } => vec![alias],
+                Stmt::FromImport { names, .. } => names.iter().collect(),
+                _ => vec![],
+            };
+            for name in names {
+                scope.hoisted.insert(name.lexeme.clone(), false);
+            }
+        }
+    }
+
+    fn is_local(&self) -> bool {
+        self.scopes.len() - 1 > self.top
+    }
+
+    fn scoped<F>(&mut self, f: F) -> Result<(), String>
+    where
+        F: FnOnce(&mut Self) -> Result<(), String>,
+    {
+        self.scopes.push(Scope::default());
+        let result = f(self);
+        let scope = self.scopes.pop().unwrap();
+        let mut unused: Vec<_> = scope
+            .bindings
+            .iter()
+            .filter(|(name, b)| b.warn_unused && !b.used && !name.starts_with('_'))
+            .collect();
+        unused.sort_by_key(|(name, b)| (b.line, name.to_string()));
+        for (name, binding) in unused {
+            self.warnings.push(format!(
+                "[line {}] Warning: local variable {} is never used",
+                binding.line, name
+            ));
+        }
+        result
+    }
+
+    // a block of statements sharing one new scope
+    fn block(&mut self, stmts: &[Stmt]) -> Result<(), String> {
+        self.scoped(|this| {
+            this.hoist(stmts);
+            this.stmts(stmts)
+        })
+    }
+
+    fn declare(&mut self, name: &Token, constant: bool, warn_unused: bool) -> Result<(), String> {
+        let local = self.is_local();
+        let scope = self.scopes.last_mut().unwrap();
+        match scope.bindings.get(&name.lexeme) {
+            Some(existing) if existing.constant => {
+                return Err(format!(
+                    "Cannot redefine constant {} at line {}",
+                    name.lexeme, name.line
+                ));
+            }
+            // a REPL line may declare a name an earlier line already did
+            Some(existing) if !existing.inherited => {
+                return Err(format!(
+                    "Variable {} is already declared in this scope at line {}",
+                    name.lexeme, name.line
+                ));
+            }
+            _ => (),
+        }
+        let used = scope.hoisted.get(&name.lexeme) == Some(&true);
+        scope.bindings.insert(
+            name.lexeme.clone(),
+            Binding {
+                constant,
+                defined: true,
+                used,
+                line: name.line,
+                warn_unused: warn_unused && local,
+                inherited: false,
+            },
+        );
+        Ok(())
+    }
+
+    fn binding(&mut self, name: &Token) -> Option<&mut Binding> {
+        self.scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.bindings.get_mut(&name.lexeme))
+    }
+
+    // finds the scope a name refers to. A function body only runs once it
+    // is called, so outside its own scopes it also sees names the enclosing
+    // code declares further down; those are marked used on the spot
+    fn lookup(&mut self, name: &Token) -> Option<usize> {
+        let start = self.functions.last().copied().unwrap_or(0);
+        for (i, scope) in self.scopes.iter_mut().enumerate().rev() {
+            if scope.bindings.contains_key(&name.lexeme) {
+                return Some(i);
+            }
+            if i < start
+                && let Some(used) = scope.hoisted.get_mut(&name.lexeme)
+            {
+                *used = true;
+                return Some(i);
+            }
+        }
+        None
+    }
+
+    fn undeclared(name: &Token) -> String {
+        format!(
+            "Variable {} is not declared at line {}",
+            name.lexeme, name.line
+        )
+    }
+
+    fn read(&mut self, name: &Token) -> Result<(), String> {
+        let scope = self.lookup(name).ok_or_else(|| Self::undeclared(name))?;
+        if let Some(binding) = self.scopes[scope].bindings.get_mut(&name.lexeme) {
+            if !binding.defined {
+                return Err(format!(
+                    "Cannot read local variable {} in its own initializer at line {}",
+                    name.lexeme, name.line
+                ));
+            }
+            binding.used = true;
+        }
+        Ok(())
+    }
+
+    fn assign(&mut self, name: &Token) -> Result<(), String> {
+        let scope = self.lookup(name).ok_or_else(|| Self::undeclared(name))?;
+        match self.scopes[scope].bindings.get(&name.lexeme) {
+            Some(binding) if binding.constant => Err(format!(
+                "Cannot assign to constant {} at line {}",
+                name.lexeme, name.line
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> Result<(), String> {
+        match stmt {
+            Stmt::Expression { expression } => self.expr(expression),
+            Stmt::Var {
+                name,
+                initializer,
+                constant,
+            } => {
+                if !self.is_local() {
+                    self.expr(initializer)?;
+                    return self.declare(name, *constant, true);
+                }
+                self.declare(name, *constant, true)?;
+                self.binding(name).unwrap().defined = false;
+                let result = self.expr(initializer);
+                self.binding(name).unwrap().defined = true;
+                result
+            }
+            Stmt::Block { stmts } => self.block(stmts),
+            Stmt::IfElse {
+                condition,
+                then,
+                els,
+            } => {
+                self.expr(condition)?;
+                self.stmt(then)?;
+                match els {
+                    Some(els) => self.stmt(els),
+                    None => Ok(()),
+                }
+            }
+            Stmt::WHILE {
+                condition,
+                block,
+                increment,
+                ..
+            } => {
+                self.expr(condition)?;
+                self.stmt(block)?;
+                match increment {
+                    Some(increment) => self.expr(increment),
+                    None => Ok(()),
+                }
+            }
+            Stmt::DoWhile {
+                body, condition, ..
+            } => {
+                self.stmt(body)?;
+                self.expr(condition)
+            }
+            Stmt::ForIn {
+                names,
+                iterable,
+                body,
+                ..
+            } => {
+                self.expr(iterable)?;
+                self.scoped(|this| {
+                    for name in names {
+                        this.declare(name, false, false)?;
+                    }
+                    this.stmt(body)
+                })
+            }
+            Stmt::Function {
+                name,
+                params,
+                rest,
+                body,
+            } => {
+                self.declare(name, false, true)?;
+                self.functions.push(self.scopes.len());
+                let result = self.scoped(|this| {
+                    for param in params {
+                        if let Some(default) = &param.default {
+                            this.expr(default)?;
+                        }
+                        this.declare(&param.name, false, false)?;
+                    }
+                    if let Some(rest) = rest {
+                        this.declare(rest, false, false)?;
+                    }
+                    this.hoist(body);
+                    this.stmts(body)
+                });
+                self.functions.pop();
+                result
+            }
+            Stmt::Return { expr, .. } => match expr {
+                Some(expr) => self.expr(expr),
+                None => Ok(()),
+            },
+            Stmt::Import { alias, .. } => self.declare(alias, false, false),
+            Stmt::FromImport { names, .. } => {
+                for name in names {
+                    self.declare(name, false, false)?;
+                }
+                Ok(())
+            }
+            Stmt::Break { .. } | Stmt::Continue { .. } => Ok(()),
+            Stmt::Throw { value, .. } => self.expr(value),
+            Stmt::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                self.block(body)?;
+                if let Some((name, handler)) = catch {
+                    self.scoped(|this| {
+                        this.declare(name, false, false)?;
+                        this.hoist(handler);
+                        this.stmts(handler)
+                    })?;
+                }
+                match finally {
+                    Some(finally) => self.block(finally),
+                    None => Ok(()),
+                }
+            }
+            Stmt::Match { value, arms } => {
+                self.expr(value)?;
+                for arm in arms {
+                    self.scoped(|this| {
+                        this.pattern(&arm.pattern)?;
+                        if let Some(guard) = &arm.guard {
+                            this.expr(guard)?;
+                        }
+                        this.stmt(&arm.body)
+                    })?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn pattern(&mut self, pattern: &Pattern) -> Result<(), String> {
+        match pattern {
+            Pattern::Wildcard | Pattern::Literal(_) => Ok(()),
+            Pattern::Binding(name) => self.declare(name, false, false),
+            Pattern::Alternatives(alts) => {
+                for alt in alts {
+                    self.pattern(alt)?;
+                }
+                Ok(())
+            }
+            Pattern::List { items, rest } => {
+                for item in items {
+                    self.pattern(item)?;
+                }
+                match rest {
+                    Some(Some(name)) => self.declare(name, false, false),
+                    _ => Ok(()),
+                }
+            }
+            Pattern::Map(entries) => {
+                for (_, pattern) in entries {
+                    self.pattern(pattern)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Result<(), String> {
+        match expr {
+            Expr::Variable { name } => self.read(name),
+            Expr::Assign { name, value } => {
+                self.expr(value)?;
+                self.assign(name)
+            }
+            Expr::CompoundAssign { target, value, .. } => {
+                self.target(target)?;
+                self.expr(value)
+            }
+            Expr::Increment { target, .. } => self.target(target),
+            Expr::Call {
+                callie,
+                args,
+                named,
+                ..
+            } => {
+                self.expr(callie)?;
+                for arg in args.iter().chain(named.iter().map(|(_, arg)| arg)) {
+                    self.expr(arg)?;
+                }
+                Ok(())
+            }
+            Expr::Get { object, .. } => self.expr(object),
+            Expr::Binary { left, right, .. }
+            | Expr::Logical {
+                expression: left,
+                right,
+                ..
+            } => {
+                self.expr(left)?;
+                self.expr(right)
+            }
+            Expr::Grouping { expression } => self.expr(expression),
+            Expr::Unary { right, .. } => self.expr(right),
+            Expr::Literal { .. } => Ok(()),
+            Expr::Ternary {
+                condition,
+                then,
+                els,
+            } => {
+                self.expr(condition)?;
+                self.expr(then)?;
+                self.expr(els)
+            }
+            Expr::List { items } => {
+                for item in items {
+                    self.expr(item)?;
+                }
+                Ok(())
+            }
+            Expr::Map { entries, .. } => {
+                for (key, value) in entries {
+                    self.expr(key)?;
+                    self.expr(value)?;
+                }
+                Ok(())
+            }
+            Expr::Index { object, index, .. } => {
+                self.expr(object)?;
+                self.expr(index)
+            }
+            Expr::IndexSet {
+                object,
+                index,
+                value,
+                ..
+            } => {
+                self.expr(object)?;
+                self.expr(index)?;
+                self.expr(value)
+            }
+        }
+    }
+
+    // compound assignment and ++/-- read the variable and write it back
+    fn target(&mut self, target: &Expr) -> Result<(), String> {
+        match target {
+            Expr::Variable { name } => {
+                self.read(name)?;
+                self.assign(name)
+            }
+            other => self.expr(other),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{expr::LiteralValue, parser::Parser, scanner::Scanner};
+
+    // resolves against a bare environment with one constant and one variable,
+    // so no builtins are in scope
+    fn resolve(source: &str) -> Result<Vec<String>, String> {
+        let mut env = Environment::new();
+        env.define_const("limit", LiteralValue::Number(3.0));
+        env.define("count", LiteralValue::Number(0.0));
+        let statements = Parser::new(Scanner::new(source.to_string()).scanTokens())
+            .parse()
+            .unwrap();
+        Resolver::new(&env).resolve(&statements)
+    }
+
+    #[test]
+    fn names_come_from_the_environment_and_earlier_declarations() {
+        assert_eq!(resolve("count = limit; var a = count; a = 1;"), Ok(vec![]));
+        assert_eq!(
+            resolve("var a = 1;\nprint(a);"),
+            Err("Variable print is not declared at line 2".to_string())
+        );
+        assert_eq!(
+            resolve("limit = 4;"),
+            Err("Cannot assign to constant limit at line 1".to_string())
+        );
+        assert_eq!(
+            resolve("fun f() { limit += 1; }"),
+            Err("Cannot assign to constant limit at line 1".to_string())
+        );
+        assert_eq!(
+            resolve("var limit = 4;"),
+            Err("Cannot redefine constant limit at line 1".to_string())
+        );
+    }
+
+    #[test]
+    fn redeclaring_is_only_allowed_for_inherited_names() {
+        // `count` comes from the environment, like a name an earlier REPL
+        // line declared, so the program may declare it again
+        assert_eq!(resolve("var count = 1;"), Ok(vec![]));
+        // but only once, after which the name is this program's own
+        assert_eq!(
+            resolve("var count = 1;\nfun count() {}"),
+            Err("Variable count is already declared in this scope at line 2".to_string())
+        );
+        assert_eq!(
+            resolve("var a = 1;\nvar a = 2;"),
+            Err("Variable a is already declared in this scope at line 2".to_string())
+        );
+        assert_eq!(
+            resolve("fun f() {}\nvar f = 1;"),
+            Err("Variable f is already declared in this scope at line 2".to_string())
+        );
+        assert_eq!(
+            resolve("fun f() {\n  var a = 1;\n  var a = 2;\n  return a;\n}"),
+            Err("Variable a is already declared in this scope at line 3".to_string())
+        );
+        // an inner block may shadow, but not read the name it is shadowing yet
+        assert_eq!(
+            resolve("{ var a = 1; { var b = a; count = b; } }"),
+            Ok(vec![])
+        );
+        assert_eq!(
+            resolve("{ var a = 1; { var a = a + 1; count = a; } }"),
+            Err("Cannot read local variable a in its own initializer at line 1".to_string())
+        );
+    }
+
+    #[test]
+    fn only_function_bodies_see_names_declared_later() {
+        assert_eq!(
+            resolve("fun f() { return later; } var later = 1; count = f();"),
+            Ok(vec![])
+        );
+        assert_eq!(
+            resolve("count = later; var later = 1;"),
+            Err("Variable later is not declared at line 1".to_string())
+        );
+        // the later local counts as used by the function that reads it
+        assert_eq!(
+            resolve("{ fun f() { return later; } var later = 1; count = f(); }"),
+            Ok(vec![])
+        );
+    }
+
+    #[test]
+    fn unused_locals_warn_in_line_order() {
+        let source = "fun f(unused_param) {
+            var b = 1;
+            var a = 2;
+            var _ignored = 3;
+            for (item in [1]) {}
+            try { count = 1; } catch (e) {}
+            match (count) { n => {} }
+            fun helper() {}
+        }
+        f(1);";
+        assert_eq!(
+            resolve(source),
+            Ok(vec![
+                "[line 2] Warning: local variable b is never used".to_string(),
+                "[line 3] Warning: local variable a is never used".to_string(),
+                "[line 8] Warning: local variable helper is never used".to_string(),
+            ])
+        );
+        // top-level variables never warn
+        assert_eq!(resolve("var unused = 1;"), Ok(vec![]));
+    }
+
+    #[test]
+    fn scoped_bindings_end_with_their_scope() {
+        for source in [
+            "for (item in [1]) {} count = item;",
+            "try {} catch (e) {} count = e;",
+            "match (1) { n => {} } count = n;",
+            "fun f(p) {} count = p;",
+            "{ var inner = 1; count = inner; } count = inner;",
+        ] {
+            assert!(
+                resolve(source)
+                    .unwrap_err()
+                    .ends_with("is not declared at line 1"),
+                "{}",
+                source
+            );
+        }
+    }
+}
@@ -1,5 +1,4 @@
-use core::panic;
-use std::{any::type_name, collections::HashMap, string, usize};
+use std::collections::HashMap;
 
 use crate::{
     token::{Literal, Token},
@@ -40,17 +39,80 @@ impl Scanner {
     fn scanToken(&mut self) {
         let c: char = self.advance();
         match c {
-            '%' => self.token_add(TokenType::Modulus),
+            '%' => {
+                let token = match self.match_token('=') {
+                    true => TokenType::MODULUS_EQUAL,
+                    false => TokenType::Modulus,
+                };
+                self.token_add(token);
+            }
             '(' => self.token_add(TokenType::LEFT_PAREN),
             ')' => self.token_add(TokenType::RIGHT_PAREN),
             '{' => self.token_add(TokenType::LEFT_BRACE),
             '}' => self.token_add(TokenType::RIGHT_BRACE),
             ',' => self.token_add(TokenType::COMMA),
-            '.' => self.token_add(TokenType::DOT),
-            '-' => self.token_add(TokenType::MINUS),
-            '+' => self.token_add(TokenType::PLUS),
+            '.' => {
+                if self.peek() == '.' && self.peek_next() == '.' {
+                    self.current += 2;
+                    self.token_add(TokenType::DOT_DOT_DOT);
+                } else if self.match_token('.') {
+                    self.token_add(TokenType::DOT_DOT);
+                } else {
+                    self.token_add(TokenType::DOT);
+                }
+            }
+            '[' => self.token_add(TokenType::LEFT_BRACKET),
+            ']' => self.token_add(TokenType::RIGHT_BRACKET),
+            '-' => {
+                let token = if self.match_token('=') {
+                    TokenType::MINUS_EQUAL
+                } else if self.match_token('-') {
+                    TokenType::MINUS_MINUS
+                } else {
+                    TokenType::MINUS
+                };
+                self.token_add(token);
+            }
+            '+' => {
+                let token = if self.match_token('=') {
+                    TokenType::PLUS_EQUAL
+                } else if self.match_token('+') {
+                    TokenType::PLUS_PLUS
+                } else {
+                    TokenType::PLUS
+                };
+                self.token_add(token);
+            }
             ';' => self.token_add(TokenType::SEMICOLON),
-            '*' => self.token_add(TokenType::STAR),
+            ':' => self.token_add(TokenType::COLON),
+            '&' => self.token_add(TokenType::AMPERSAND),
+            '|' => self.token_add(TokenType::PIPE),
+            '^' => self.token_add(TokenType::CARET),
+            // `//` always starts a comment, so floor division is `~/`
+            '~' => {
+                let token = match self.match_token('/') {
+                    true => TokenType::TILDE_SLASH,
+                    false => TokenType::TILDE,
+                };
+                self.token_add(token);
+            }
+            '?' => {
+                let token = match self.match_token('?') {
+                    true => TokenType::QUESTION_QUESTION,
+                    false => TokenType::QUESTION,
+                };
+                self.token_add(token);
+            }
+            '*' => {
+                let token = if self.match_token('=') {
+                    TokenType::STAR_EQUAL
+                } else if self.match_token('*') {
+                    TokenType::STAR_STAR
+                } else {
+                    TokenType::STAR
+                };
+                self.token_add(token);
+            }
             '!' => {
                 let token = match self.match_token('=') {
                     true => TokenType::BANG_EQUAL,
@@ -59,23 +121,32 @@ impl Scanner {
                 self.token_add(token);
             }
             '=' => {
-                let token = match self.match_token('=') {
-                    true => TokenType::EQUAL_EQUAL,
-                    false => TokenType::EQUAL,
+                let token = if self.match_token('=') {
+                    TokenType::EQUAL_EQUAL
+                } else if self.match_token('>') {
+                    TokenType::FAT_ARROW
+                } else {
+                    TokenType::EQUAL
                 };
                 self.token_add(token);
             }
             '>' => {
-                let token = match self.match_token('=') {
-                    true => TokenType::GREATER_EQUAL,
-                    false => TokenType::GREATER,
+                let token = if self.match_token('=') {
+                    TokenType::GREATER_EQUAL
+                } else if self.match_token('>') {
+                    TokenType::GREATER_GREATER
+                } else {
+                    TokenType::GREATER
                 };
                 self.token_add(token);
             }
             '<' => {
-                let token = match self.match_token('=') {
-                    true => TokenType::LESS_EQUAL,
-                    false => TokenType::LESS,
+                let token = if self.match_token('=') {
+                    TokenType::LESS_EQUAL
+                } else if self.match_token('<') {
+                    TokenType::LESS_LESS
+                } else {
+                    TokenType::LESS
                 };
                 self.token_add(token);
             }
@@ -86,6 +157,7 @@ impl Scanner {
                             self.advance();
                         }
                     }
+                    false if self.match_token('=') => self.token_add(TokenType::SLASH_EQUAL),
                     false => self.token_add(TokenType::SLASH),
                 };
             }
@@ -106,11 +178,12 @@ impl Scanner {
         }
         let key = &self.source[self.start..self.current];
 
-        let mut t = self.keywords.get(key).cloned();
-        if let None = t {
-            t = Some(TokenType::IDENTIFIER);
-        }
-        self.token_add(t.unwrap());
+        let t = self
+            .keywords
+            .get(key)
+            .cloned()
+            .unwrap_or(TokenType::IDENTIFIER);
+        self.token_add(t);
     }
 
     fn number(&mut self) {
@@ -136,6 +209,9 @@ impl Scanner {
             if self.peek() == '\n' {
                 self.line += 1;
             }
+            if self.peek() == '\\' && self.current + 1 < self.source.len() {
+                self.advance();
+            }
             self.advance();
         }
         if self.is_at_end() {
@@ -143,10 +219,30 @@ impl Scanner {
             return;
         }
         self.advance();
-        let string = self.source[self.start + 1..self.current - 1].to_owned();
+        let string = Self::unescape(&self.source[self.start + 1..self.current - 1]);
         self.add_token(TokenType::STRING, Some(Literal::StringLiteral(string)));
     }
 
+    fn unescape(raw: &str) -> String {
+        let mut out = String::with_capacity(raw.len());
+        let mut chars = raw.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                out.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some('r') => out.push('\r'),
+                Some('0') => out.push('\0'),
+                Some(other) => out.push(other),
+                None => out.push('\\'),
+            }
+        }
+        out
+    }
+
     fn peek(&self) -> char {
         if self.is_at_end() {
             return '\0';
@@ -154,7 +250,7 @@ impl Scanner {
         self.source.as_bytes()[self.current] as char
     }
     fn advance(&mut self) -> char {
-        let char = self.source.as_bytes()[self.current as usize];
+        let char = self.source.as_bytes()[self.current];
         self.current += 1;
         char as char
     }
@@ -167,10 +263,7 @@ impl Scanner {
         if self.current + 1 >= self.source.len() {
             '\0'
         } else {
-            self.source
-                .chars()
-                .nth((self.current + 1) as usize)
-                .unwrap()
+            self.source.as_bytes()[self.current + 1] as char
         }
     }
 
@@ -187,7 +280,7 @@ impl Scanner {
     }
 
     fn add_token(&mut self, type_token: TokenType, literal: Option<Literal>) {
-        let text = self.source[self.start as usize..self.current as usize].to_string();
+        let text = self.source[self.start..self.current].to_string();
         self.tokens
             .push(Token::new(type_token, text, literal, self.line));
     }
@@ -203,7 +296,6 @@ impl Scanner {
         keywords.insert("if", TokenType::IF);
         keywords.insert("nil", TokenType::NIL);
         keywords.insert("or", TokenType::OR);
-        keywords.insert("print", TokenType::PRINT);
         keywords.insert("return", TokenType::RETURN);
         keywords.insert("super", TokenType::SUPER);
         keywords.insert("this", TokenType::THIS);
@@ -212,11 +304,30 @@ impl Scanner {
         keywords.insert("while", TokenType::WHILE);
         keywords.insert("break", TokenType::BREAK);
         keywords.insert("continue", TokenType::CONTINUE);
+        keywords.insert("try", TokenType::TRY);
+        keywords.insert("catch", TokenType::CATCH);
+        keywords.insert("finally", TokenType::FINALLY);
+        keywords.insert("throw", TokenType::THROW);
+        keywords.insert("import", TokenType::IMPORT);
+        keywords.insert("from", TokenType::FROM);
+        keywords.insert("as", TokenType::AS);
+        keywords.insert("match", TokenType::MATCH);
+        keywords.insert("in", TokenType::IN);
+        keywords.insert("do", TokenType::DO);
+        keywords.insert("const", TokenType::CONST);
         keywords
     }
 
+    // whether `name` would scan as a single identifier
+    pub fn is_identifier(name: &str) -> bool {
+        let mut chars = name.chars();
+        chars.next().is_some_and(Self::is_alpha)
+            && chars.all(Self::is_alpha_numeric)
+            && !Self::init_keywords().contains_key(name)
+    }
+
     fn is_alpha(c: char) -> bool {
-        c >= 'a' && c <= 'z' || c >= 'A' && c <= 'Z'
+        c.is_ascii_alphabetic() || c == '_'
     }
 
     fn is_alpha_numeric(c: char) -> bool {
@@ -224,6 +335,36 @@ impl Scanner {
     }
 
     fn is_digit(c: char) -> bool {
-        c >= '0' && c <= '9'
+        c.is_ascii_digit()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn types(source: &str) -> Vec<TokenType> {
+        Scanner::new(source.to_string())
+            .scanTokens()
+            .into_iter()
+            .map(|token| token.token_type)
+            .collect()
+    }
+
+    #[test]
+    fn trailing_comments_after_values_are_comments() {
+        use TokenType::*;
+        assert_eq!(
+            types("[\n 1, // first\n 2  // second\n]"),
+            [LEFT_BRACKET, NUMBER, COMMA, NUMBER, RIGHT_BRACKET, EOF]
+        );
+        assert_eq!(
+            types("var t = 10 // the base price\n + 5;"),
+            [VAR, IDENTIFIER, EQUAL, NUMBER, PLUS, NUMBER, SEMICOLON, EOF]
+        );
+        assert_eq!(
+            types("7 ~/ 2 ~ 1"),
+            [NUMBER, TILDE_SLASH, NUMBER, TILDE, NUMBER, EOF]
+        );
     }
 }
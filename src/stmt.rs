@@ -1,15 +1,17 @@
-use crate::{expr::Expr, token::Token};
-#[derive(Clone,Debug)]
+use crate::{
+    expr::{Expr, LiteralValue},
+    token::Token,
+};
+#[derive(Clone, Debug)]
 pub enum Stmt {
     Expression {
         expression: Expr,
     },
-    Print {
-        expression: Expr,
-    },
     Var {
         name: Token,
         initializer: Expr,
+        // declared with `const`, so it can't be reassigned
+        constant: bool,
     },
     Block {
         stmts: Vec<Stmt>,
@@ -22,16 +24,114 @@ pub enum Stmt {
     WHILE {
         condition: Expr,
         block: Box<Stmt>,
+        // the `for` loop step, run after the body and after `continue`
+        increment: Option<Expr>,
+        label: Option<String>,
+    },
+    DoWhile {
+        body: Box<Stmt>,
+        condition: Expr,
+        label: Option<String>,
+    },
+    // for (x in xs) / for (k, v in map)
+    ForIn {
+        names: Vec<Token>,
+        keyword: Token,
+        iterable: Expr,
+        body: Box<Stmt>,
+        label: Option<String>,
+    },
+    Function {
+        name: Token,
+        params: Vec<Param>,
+        // `...rest` collects the remaining positional arguments into a list
+        rest: Option<Token>,
+        body: Vec<Stmt>,
+    },
+    Return {
+        #[allow(dead_code)]
+        token: Token,
+        expr: Option<Expr>,
+    },
+    Import {
+        keyword: Token,
+        path: String,
+        alias: Token,
     },
-    Function{
-        name:Token,
-        params: Vec<Token>,
-        body: Vec<Stmt>
+    FromImport {
+        keyword: Token,
+        path: String,
+        names: Vec<Token>,
     },
-    Return{
-        token:Token,
-        expr:Option<Expr>
+    // `break outer;` names the loop to leave
+    Break {
+        label: Option<String>,
     },
-    Break,
-    Continue,
+    Continue {
+        label: Option<String>,
+    },
+    Throw {
+        keyword: Token,
+        value: Expr,
+    },
+    Try {
+        body: Vec<Stmt>,
+        catch: Option<(Token, Vec<Stmt>)>,
+        finally: Option<Vec<Stmt>>,
+    },
+    Match {
+        value: Expr,
+        arms: Vec<MatchArm>,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct Param {
+    pub name: Token,
+    pub default: Option<Expr>,
+}
+
+#[derive(Clone, Debug)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<Expr>,
+    pub body: Stmt,
+}
+
+#[derive(Clone, Debug)]
+pub enum Pattern {
+    Wildcard,
+    Literal(LiteralValue),
+    Binding(Token),
+    Alternatives(Vec<Pattern>),
+    // `[a, b, ...rest]`; `rest` is `Some(None)` for a bare `...`
+    List {
+        items: Vec<Pattern>,
+        rest: Option<Option<Token>>,
+    },
+    // `{"k": p}` matches maps that have at least these keys
+    Map(Vec<(String, Pattern)>),
+}
+
+impl Pattern {
+    // matches every value, so later arms can never run
+    pub fn is_irrefutable(&self) -> bool {
+        match self {
+            Pattern::Wildcard | Pattern::Binding(_) => true,
+            Pattern::Alternatives(alts) => alts.iter().any(|p| p.is_irrefutable()),
+            _ => false,
+        }
+    }
+
+    pub fn binds_names(&self) -> bool {
+        match self {
+            Pattern::Binding(_) => true,
+            Pattern::Wildcard | Pattern::Literal(_) => false,
+            Pattern::Alternatives(alts) => alts.iter().any(|p| p.binds_names()),
+            Pattern::List { items, rest } => {
+                matches!(rest, Some(Some(_))) || items.iter().any(|p| p.binds_names())
+            }
+            Pattern::Map(entries) => entries.iter().any(|(_, p)| p.binds_names()),
+        }
+    }
 }
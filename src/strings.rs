@@ -0,0 +1,273 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    builtins::{Builtin, number, string},
+    environment::Environment,
+    error::RuntimeError,
+    expr::{Arity, LiteralValue},
+};
+
+type EnvRef = Rc<RefCell<Environment>>;
+
+// `"text".upper()`: looks up a string method and binds the receiver, which
+// the implementation gets as its first argument
+pub fn method(receiver: &str, name: &str) -> Option<LiteralValue> {
+    let (arity, fun): (Arity, Builtin) = match name {
+        "len" => (Arity::exact(0), len),
+        "chars" => (Arity::exact(0), chars),
+        "substring" => (Arity::between(1, 2), substring),
+        "find" => (Arity::exact(1), find),
+        "contains" => (Arity::exact(1), contains),
+        "starts_with" => (Arity::exact(1), starts_with),
+        "ends_with" => (Arity::exact(1), ends_with),
+        "split" => (Arity::between(0, 1), split),
+        "join" => (Arity::exact(1), join),
+        "replace" => (Arity::exact(2), replace),
+        "trim" => (Arity::exact(0), |_, args| {
+            map(args, |s| s.trim().to_string())
+        }),
+        "trim_start" => (Arity::exact(0), |_, args| {
+            map(args, |s| s.trim_start().to_string())
+        }),
+        "trim_end" => (Arity::exact(0), |_, args| {
+            map(args, |s| s.trim_end().to_string())
+        }),
+        "upper" => (Arity::exact(0), |_, args| map(args, str::to_uppercase)),
+        "lower" => (Arity::exact(0), |_, args| map(args, str::to_lowercase)),
+        "repeat" => (Arity::exact(1), repeat),
+        "parse_number" => (Arity::exact(0), parse_number),
+        _ => return None,
+    };
+    let receiver = LiteralValue::StringValue(receiver.to_string());
+    Some(LiteralValue::Callable {
+        name: name.to_string(),
+        arity,
+        params: Rc::from([]),
+        keywords: Rc::from([]),
+        fun: Rc::new(move |env, args| {
+            let mut all = Vec::with_capacity(args.len() + 1);
+            all.push(receiver.clone());
+            all.extend_from_slice(args);
+            fun(env, &all)
+        }),
+    })
+}
+
+fn receiver(args: &[LiteralValue]) -> &str {
+    match &args[0] {
+        LiteralValue::StringValue(s) => s,
+        _ => unreachable!("string methods are only bound to strings"),
+    }
+}
+
+fn map(args: &[LiteralValue], f: fn(&str) -> String) -> Result<LiteralValue, RuntimeError> {
+    Ok(LiteralValue::StringValue(f(receiver(args))))
+}
+
+// the characters in `start..end`, counted in chars rather than bytes;
+// negative bounds count from the end
+pub fn slice(s: &str, start: f64, end: f64) -> Result<String, String> {
+    let len = s.chars().count() as f64;
+    let bound = |i: f64| if i < 0.0 { i + len } else { i };
+    let (from, to) = (bound(start), bound(end));
+    if start.fract() != 0.0 || end.fract() != 0.0 || from < 0.0 || from > to || to > len {
+        return Err(format!(
+            "Slice {}..{} out of range for length {}",
+            start, end, len
+        ));
+    }
+    Ok(s.chars()
+        .skip(from as usize)
+        .take((to - from) as usize)
+        .collect())
+}
+
+// byte offset -> char index
+fn char_index(s: &str, byte: usize) -> usize {
+    s[..byte].chars().count()
+}
+
+fn len(_env: EnvRef, args: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    Ok(LiteralValue::Number(receiver(args).chars().count() as f64))
+}
+
+fn chars(_env: EnvRef, args: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    Ok(LiteralValue::list(
+        receiver(args)
+            .chars()
+            .map(|c| LiteralValue::StringValue(c.to_string()))
+            .collect(),
+    ))
+}
+
+fn substring(_env: EnvRef, args: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let s = receiver(args);
+    let start = number("substring", &args[1])?;
+    let end = match args.get(2) {
+        Some(end) => number("substring", end)?,
+        None => s.chars().count() as f64,
+    };
+    Ok(LiteralValue::StringValue(slice(s, start, end)?))
+}
+
+// char index of the first match, -1 when there is none
+fn find(_env: EnvRef, args: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let s = receiver(args);
+    let needle = string("find", &args[1])?;
+    Ok(LiteralValue::Number(match s.find(needle) {
+        Some(byte) => char_index(s, byte) as f64,
+        None => -1.0,
+    }))
+}
+
+fn contains(_env: EnvRef, args: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let needle = string("contains", &args[1])?;
+    Ok(LiteralValue::from_bool(receiver(args).contains(needle)))
+}
+
+fn starts_with(_env: EnvRef, args: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let prefix = string("starts_with", &args[1])?;
+    Ok(LiteralValue::from_bool(receiver(args).starts_with(prefix)))
+}
+
+fn ends_with(_env: EnvRef, args: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let suffix = string("ends_with", &args[1])?;
+    Ok(LiteralValue::from_bool(receiver(args).ends_with(suffix)))
+}
+
+// without a separator, splits on runs of whitespace
+fn split(_env: EnvRef, args: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let s = receiver(args);
+    let parts: Vec<&str> = match args.get(1) {
+        None => s.split_whitespace().collect(),
+        Some(sep) => match string("split", sep)? {
+            "" => return Err("split separator can't be empty".to_string().into()),
+            sep => s.split(sep).collect(),
+        },
+    };
+    Ok(LiteralValue::list(
+        parts
+            .into_iter()
+            .map(|p| LiteralValue::StringValue(p.to_string()))
+            .collect(),
+    ))
+}
+
+// `", ".join(items)`, with non-string items shown as print would
+fn join(_env: EnvRef, args: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let LiteralValue::List(items) = &args[1] else {
+        return Err(format!("join expected a list found {}", args[1].to_type()).into());
+    };
+    let parts: Vec<String> = items.borrow().iter().map(|v| v.to_string()).collect();
+    Ok(LiteralValue::StringValue(parts.join(receiver(args))))
+}
+
+fn replace(_env: EnvRef, args: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let from = string("replace", &args[1])?;
+    let to = string("replace", &args[2])?;
+    if from.is_empty() {
+        return Err("replace pattern can't be empty".to_string().into());
+    }
+    Ok(LiteralValue::StringValue(receiver(args).replace(from, to)))
+}
+
+// 1 GiB, well before `String::repeat` would abort on capacity overflow
+const MAX_REPEAT: usize = 1 << 30;
+
+fn repeat(_env: EnvRef, args: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let times = number("repeat", &args[1])?;
+    if times < 0.0 || times.fract() != 0.0 {
+        return Err(format!("repeat count must be a whole number, got {}", times).into());
+    }
+    let s = receiver(args);
+    // `as` saturates, so an enormous count still fails the check
+    match s.len().checked_mul(times as usize) {
+        Some(len) if len <= MAX_REPEAT => Ok(LiteralValue::StringValue(s.repeat(times as usize))),
+        _ => Err(format!(
+            "repeat would make a string longer than {} bytes",
+            MAX_REPEAT
+        )
+        .into()),
+    }
+}
+
+// nil when the text isn't a number, so `s.parse_number() ?? 0` works
+fn parse_number(_env: EnvRef, args: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    Ok(match receiver(args).trim().parse::<f64>() {
+        Ok(x) => LiteralValue::Number(x),
+        Err(_) => LiteralValue::Nil,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the receiver stays a string; arguments that look numeric become numbers
+    fn call(fun: Builtin, args: &[&str]) -> Result<LiteralValue, RuntimeError> {
+        let args: Vec<LiteralValue> = args
+            .iter()
+            .enumerate()
+            .map(|(i, s)| match s.parse::<f64>() {
+                Ok(x) if i > 0 => LiteralValue::Number(x),
+                _ => LiteralValue::StringValue(s.to_string()),
+            })
+            .collect();
+        fun(Rc::new(RefCell::new(Environment::new())), &args)
+    }
+
+    fn ok(fun: Builtin, args: &[&str]) -> String {
+        call(fun, args).unwrap().to_string()
+    }
+
+    #[test]
+    fn slices_count_characters_and_accept_negative_bounds() {
+        assert_eq!(slice("naïve", 1.0, 3.0), Ok("aï".to_string()));
+        assert_eq!(slice("naïve", -3.0, -1.0), Ok("ïv".to_string()));
+        assert_eq!(slice("abc", 3.0, 3.0), Ok(String::new()));
+        for (start, end) in [(2.0, 1.0), (0.0, 4.0), (-4.0, 1.0), (0.5, 1.0)] {
+            assert!(slice("abc", start, end).is_err(), "{}..{}", start, end);
+        }
+        assert_eq!(ok(substring, &["héllo", "1"]), "éllo");
+    }
+
+    #[test]
+    fn searching_reports_character_positions() {
+        assert_eq!(ok(find, &["añob", "b"]), "3");
+        assert_eq!(ok(find, &["abc", "z"]), "-1");
+        assert_eq!(ok(contains, &["abc", ""]), "true");
+        assert_eq!(ok(starts_with, &["abc", "ab"]), "true");
+        assert_eq!(ok(ends_with, &["abc", "ab"]), "false");
+    }
+
+    #[test]
+    fn splitting_and_replacing() {
+        assert_eq!(ok(split, &["  a  b\tc "]), r#"["a", "b", "c"]"#);
+        assert_eq!(ok(split, &["a,,b", ","]), r#"["a", "", "b"]"#);
+        assert!(call(split, &["a", ""]).is_err());
+        assert_eq!(ok(replace, &["aaa", "a", "bb"]), "bbbbbb");
+        assert!(call(replace, &["aaa", "", "b"]).is_err());
+    }
+
+    #[test]
+    fn repeat_rejects_bad_and_oversized_counts() {
+        assert_eq!(ok(repeat, &["ab", "3"]), "ababab");
+        assert_eq!(ok(repeat, &["ab", "0"]), "");
+        for count in ["-1", "1.5", "inf", "1e300"] {
+            assert!(call(repeat, &["ab", count]).is_err(), "{}", count);
+        }
+        assert_eq!(
+            call(repeat, &["ab", "1e300"]).map_err(|e| e.to_string()),
+            Err("[line 0] repeat would make a string longer than 1073741824 bytes".to_string())
+        );
+        // an empty string can repeat any number of times
+        assert_eq!(ok(repeat, &["", "1e300"]), "");
+    }
+
+    #[test]
+    fn parse_number_gives_nil_for_bad_input() {
+        assert_eq!(ok(parse_number, &[" 2.5 "]), "2.5");
+        assert_eq!(ok(parse_number, &["1e3"]), "1000");
+        assert_eq!(ok(parse_number, &["12abc"]), "nil");
+    }
+}
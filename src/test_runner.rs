@@ -0,0 +1,143 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{interpreter::Interpreter, parser::Parser, scanner::Scanner, stmt::Stmt};
+
+// `lox_lang test <dir>`: every top-level `test_*` function in the .nox files
+// under `dir` runs in its own interpreter, after the rest of its file.
+// The report goes to `out`; returns whether everything passed.
+pub fn run_tests(dir: &Path, out: &mut impl Write) -> Result<bool, String> {
+    let mut files = vec![];
+    collect(dir, &mut files)?;
+    let (mut passed, mut failed) = (0, 0);
+    for file in &files {
+        let source = fs::read_to_string(file)
+            .map_err(|e| format!("Could not read {}: {}", file.display(), e))?;
+        let mut parser = Parser::new(Scanner::new(source).scanTokens());
+        let checked = parser
+            .parse()
+            .and_then(|statements| Ok((Interpreter::new().resolve(&statements)?, statements)));
+        let statements = match checked {
+            Ok((warnings, statements)) => {
+                for warning in parser.warnings.iter().chain(&warnings) {
+                    eprintln!("In {}: {}", file.display(), warning);
+                }
+                statements
+            }
+            Err(e) => {
+                writeln!(out, "FAIL {}\n    {}", file.display(), e).map_err(report_error)?;
+                failed += 1;
+                continue;
+            }
+        };
+        for name in test_names(&statements) {
+            let mut interpreter = Interpreter::new();
+            interpreter.set_script(file);
+            let result = interpreter
+                .interpret_stmt(&statements)
+                .and_then(|_| interpreter.call_function(name));
+            match result {
+                Ok(_) => {
+                    writeln!(out, "PASS {}::{}", file.display(), name).map_err(report_error)?;
+                    passed += 1;
+                }
+                Err(e) => {
+                    writeln!(out, "FAIL {}::{}\n    {}", file.display(), name, e)
+                        .map_err(report_error)?;
+                    failed += 1;
+                }
+            }
+        }
+    }
+    writeln!(out, "\n{} passed, {} failed", passed, failed).map_err(report_error)?;
+    Ok(failed == 0)
+}
+
+fn report_error(e: io::Error) -> String {
+    format!("Could not write the test report: {}", e)
+}
+
+fn test_names(statements: &[Stmt]) -> Vec<&str> {
+    statements
+        .iter()
+        .filter_map(|stmt| match stmt {
+            Stmt::Function { name, .. } if name.lexeme.starts_with("test_") => {
+                Some(name.lexeme.as_str())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+// .nox files under `dir`, in a stable order
+fn collect(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries =
+        fs::read_dir(dir).map_err(|e| format!("Could not list {}: {}", dir.display(), e))?;
+    let mut paths = entries
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Could not list {}: {}", dir.display(), e))?;
+    paths.sort();
+    for path in paths {
+        if path.is_dir() {
+            collect(&path, files)?;
+        } else if path.extension().is_some_and(|ext| ext == "nox") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(dir: &Path) -> (Result<bool, String>, String) {
+        let mut out = vec![];
+        let result = run_tests(dir, &mut out);
+        (result, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn failing_tests_fail_the_run() {
+        let dir = std::env::temp_dir().join(format!("nox-tests-{}", std::process::id()));
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        fs::write(
+            dir.join("a.nox"),
+            "fun helper() {}\nfun test_ok() { assert_eq(1 + 1, 2); }",
+        )
+        .unwrap();
+        let passing = report(&dir);
+        fs::write(
+            dir.join("nested/b.nox"),
+            "fun test_bad() {\n  assert(false);\n}",
+        )
+        .unwrap();
+        fs::write(dir.join("nested/notes.txt"), "fun test_skipped() {}").unwrap();
+        let failing = report(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+        let missing = report(&dir);
+
+        let (a, b) = (dir.join("a.nox"), dir.join("nested/b.nox"));
+        assert_eq!(passing.0, Ok(true));
+        assert_eq!(
+            passing.1,
+            format!("PASS {}::test_ok\n\n1 passed, 0 failed\n", a.display())
+        );
+        assert_eq!(failing.0, Ok(false));
+        assert_eq!(
+            failing.1,
+            format!(
+                "PASS {}::test_ok\nFAIL {}::test_bad\n    [line 2] Assertion failed\n\n\
+                 1 passed, 1 failed\n",
+                a.display(),
+                b.display()
+            )
+        );
+        assert!(missing.0.unwrap_err().starts_with("Could not list"));
+        assert_eq!(missing.1, "");
+    }
+}
@@ -1,7 +1,8 @@
-use std::fmt::format;
+use std::fmt;
 
 use crate::tokentype::TokenType;
 
+#[allow(dead_code, clippy::enum_variant_names)]
 #[derive(Debug, Clone)]
 pub enum Literal {
     StringLiteral(String),
@@ -18,9 +19,10 @@ pub struct Token {
     pub line: usize,
 }
 
-impl ToString for Token {
-    fn to_string(&self) -> String {
-        format!(
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
             " {:?}  {}  {:?}",
             self.token_type, self.lexeme, self.literal
         )
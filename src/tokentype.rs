@@ -5,12 +5,19 @@ pub enum TokenType {
     RIGHT_PAREN,
     LEFT_BRACE,
     RIGHT_BRACE,
+    LEFT_BRACKET,
+    RIGHT_BRACKET,
     COMMA,
     DOT,
+    DOT_DOT,
+    DOT_DOT_DOT,
     Modulus,
     MINUS,
     PLUS,
     SEMICOLON,
+    COLON,
+    QUESTION,
+    QUESTION_QUESTION,
     SLASH,
     STAR,
     // One or two character tokens.
@@ -18,10 +25,26 @@ pub enum TokenType {
     BANG_EQUAL,
     EQUAL,
     EQUAL_EQUAL,
+    FAT_ARROW,
     GREATER,
     GREATER_EQUAL,
     LESS,
     LESS_EQUAL,
+    PLUS_EQUAL,
+    MINUS_EQUAL,
+    STAR_EQUAL,
+    SLASH_EQUAL,
+    MODULUS_EQUAL,
+    PLUS_PLUS,
+    MINUS_MINUS,
+    STAR_STAR,
+    TILDE_SLASH,
+    AMPERSAND,
+    PIPE,
+    CARET,
+    TILDE,
+    LESS_LESS,
+    GREATER_GREATER,
     // Literals.
     IDENTIFIER,
     STRING,
@@ -36,7 +59,6 @@ pub enum TokenType {
     IF,
     NIL,
     OR,
-    PRINT,
     RETURN,
     SUPER,
     THIS,
@@ -45,9 +67,17 @@ pub enum TokenType {
     WHILE,
     EOF,
 
-
     BREAK,
     CONTINUE,
-
-
+    TRY,
+    CATCH,
+    FINALLY,
+    THROW,
+    IMPORT,
+    FROM,
+    AS,
+    MATCH,
+    IN,
+    DO,
+    CONST,
 }